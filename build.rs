@@ -0,0 +1,27 @@
+//! With the `capi` feature, generates the C/C++ header `src/ndarray_to_img.h`
+//! from the FFI types and functions in the crate, see `cbindgen.toml`.
+//! Without it, as when the crate is a dependency, nothing is written.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "capi")]
+    write_header();
+}
+
+#[cfg(feature = "capi")]
+fn write_header() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file("cbindgen.toml").unwrap();
+
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("[ndarray-to-img::build] unable to generate the C header")
+        .write_to_file("src/ndarray_to_img.h");
+}
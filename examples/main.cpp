@@ -1,13 +1,14 @@
+#include <cstdio>
 #include <vector>
 #include "../src/ndarray_to_img.h"
 
 void do_cells() {
-  color_t black (0, 0, 0, 0);
-  position_t up (20, 10);
+  color_t black = {0, 0, 0, 0};
+  position_t up = {20, 10};
   cell_t c1 = cell_t{up, 10, black};
 
-  color_t white (255, 255, 255, 0);
-  position_t down (30, 50);
+  color_t white = {255, 255, 255, 0};
+  position_t down = {30, 50};
   cell_t c2 = cell_t{down, 10, white};
 
   std::vector<cell_t> v = {c1, c2};
@@ -15,6 +16,27 @@ void do_cells() {
   read_cells(v.data(), 2, 100, 100);
 }
 
+// fill in a matrix one cell at a time, as an aligner would
+void do_matrix() {
+  matrix_t *matrix = nullptr;
+  if (matrix_new(100, 100, &matrix) != NDARRAY_TO_IMG_OK) {
+    std::fprintf(stderr, "%s\n", last_error_message());
+    return;
+  }
+
+  color_t blue = {0, 0, 255, 255};
+  for (uint32_t i = 0; i < 100; i++) {
+    matrix_set_cell(matrix, cell_t{position_t{i, i}, (int) i, blue});
+  }
+
+  if (matrix_plot(matrix, "build/matrix.png") != NDARRAY_TO_IMG_OK) {
+    std::fprintf(stderr, "%s\n", last_error_message());
+  }
+
+  matrix_free(matrix);
+}
+
 int main() {
 	do_cells();
+	do_matrix();
 }
@@ -0,0 +1,185 @@
+//! Animations
+//!
+//! Turn a sequence of matrices, for example the states of a DP matrix as it
+//! fills in, into an animated GIF or APNG.
+//! All frames share one color scale so a color means the same value in
+//! every frame.
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use image::{Delay, Frame, Rgba, RgbaImage};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::error::{
+    EncodingError, ImageError, ImageFormatHint, ImageResult, ParameterError, ParameterErrorKind
+};
+use num::cast::ToPrimitive;
+
+use crate::plot::Plottable;
+use crate::scale::ColorScale;
+use crate::types::Config;
+
+
+/// Container format of the animation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    /// Colors are quantized to a 256 color palette and drawn over white
+    Gif,
+    /// Lossless, keeps the alpha channel
+    Apng,
+}
+
+pub(crate) fn png_error(e: png::EncodingError) -> ImageError {
+    ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(image::ImageFormat::Png), e))
+}
+
+/// GIF has no partial transparency so blend every pixel over white
+fn flatten_on_white(img: &mut RgbaImage) {
+    for pixel in img.pixels_mut() {
+        let alpha = pixel[3] as f64 / u8::MAX as f64;
+        let blend = |c: u8| (c as f64 * alpha + u8::MAX as f64 * (1.0 - alpha)).round() as u8;
+        *pixel = Rgba([blend(pixel[0]), blend(pixel[1]), blend(pixel[2]), u8::MAX]);
+    }
+}
+
+/// Generate an animation with one frame per matrix in `frames`.
+///
+/// Frames are **unscaled** matrices of the same dimensions, each is scaled by
+/// the `scaling_factor` in [Config](crate::Config) before rendering.
+/// Colors follow a [shared](crate::scale::ColorScale::shared) scale over all
+/// frames.
+/// `frame_delay_ms` is the time each frame is shown for. Animations loop forever.
+pub fn animate<T, P, I>(
+    frames: I,
+    config: &Config,
+    format: AnimationFormat,
+    frame_delay_ms: u16,
+    output_path: &str
+) -> ImageResult<()>
+where
+    T: ToPrimitive + num::Zero + Copy + PartialOrd,
+    P: Plottable<T>,
+    I: IntoIterator<Item = P>
+{
+    if config.verbosity > 0 {
+        eprintln!("Generating animation {}", output_path);
+    }
+
+    // we need the color scale before rendering the first frame
+    let frames: Vec<P> = frames.into_iter().collect();
+
+    let first = match frames.first() {
+        Some(f) => f,
+        None => {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::Generic("[ndarray-to-img::animate] no frames to animate".to_string())
+            )));
+        }
+    };
+
+    if frames.iter().any(|f| f.dim() != first.dim()) {
+        return Err(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch)));
+    }
+
+    let scale = ColorScale::shared(&frames, config);
+
+    if config.verbosity > 1 {
+        eprintln!("frames: {}", frames.len());
+    }
+
+    let mut images = frames
+        .iter()
+        .map(|f| f.scaled(config).render_with_scale(config, &scale))
+        .peekable();
+
+    let file = BufWriter::new(File::create(output_path)?);
+
+    match format {
+        AnimationFormat::Gif => {
+            let mut encoder = GifEncoder::new(file);
+            encoder.set_repeat(Repeat::Infinite)?;
+            let delay = Delay::from_numer_denom_ms(frame_delay_ms as u32, 1);
+            encoder.encode_frames(images.map(|mut img| {
+                flatten_on_white(&mut img);
+                Frame::from_parts(img, 0, 0, delay)
+            }))?;
+        },
+        AnimationFormat::Apng => {
+            // all frames are the same size as the first
+            let (width, height) = images.peek().map(|img| img.dimensions()).unwrap();
+
+            let mut encoder = png::Encoder::new(file, width, height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(frames.len() as u32, 0).map_err(png_error)?;
+            encoder.set_frame_delay(frame_delay_ms, 1000).map_err(png_error)?;
+
+            let mut writer = encoder.write_header().map_err(png_error)?;
+            for img in images {
+                writer.write_image_data(&img).map_err(png_error)?;
+            }
+            writer.finish().map_err(png_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::plot::OptMatrix;
+    use crate::tests_prelude;
+    use ndarray::{Array, Array2};
+
+    /// A matrix filling in one anti-diagonal per frame
+    fn wavefront_frames() -> Vec<OptMatrix<i32>> {
+        let mut frames = Vec::new();
+		let mut matrix: Array2<Option<i32>> = Array::from_elem((10, 10), None);
+
+        for step in 0..10 {
+            for i in 0..=step {
+                matrix[[i, step - i]] = Some(step as i32 - 5);
+            }
+            frames.push(OptMatrix { matrix: matrix.clone() });
+        }
+
+        frames
+    }
+
+    #[test]
+    fn test_animate_gif() {
+		let config = tests_prelude::config();
+		let name = "test_animation.gif";
+
+        assert_eq!(animate(wavefront_frames(), &config, AnimationFormat::Gif, 200, name).unwrap(), ());
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(name).unwrap(), ());
+		}
+    }
+
+    #[test]
+    fn test_animate_apng() {
+		let config = tests_prelude::config();
+		let name = "test_animation.png";
+
+        assert_eq!(animate(wavefront_frames(), &config, AnimationFormat::Apng, 200, name).unwrap(), ());
+
+        // the animation control chunk marks an APNG
+        let bytes = std::fs::read(name).unwrap();
+        assert!(bytes.windows(4).any(|w| w == b"acTL"));
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(name).unwrap(), ());
+		}
+    }
+
+    #[test]
+    fn test_animate_no_frames() {
+        let frames: Vec<OptMatrix<i32>> = Vec::new();
+        let config = tests_prelude::config();
+        assert!(animate(frames, &config, AnimationFormat::Gif, 200, "test_empty.gif").is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! Axis labels
+//!
+//! Row and column indices drawn in a margin to the left and above an image.
+
+use image::{imageops, RgbaImage};
+
+use crate::constants::colors::*;
+use crate::font::{draw_text, text_width, GLYPH_HEIGHT};
+
+/// Space around labels
+const PADDING: u32 = 3;
+
+/// The smallest of 1, 2, 5, 10, 20, 50... cells spanning at least `min_pixels`
+fn tick_step(min_pixels: u32, scaling_factor: u32) -> usize {
+    let mut magnitude = 1;
+    loop {
+        for step in [1, 2, 5] {
+            if step * magnitude * scaling_factor as usize >= min_pixels as usize {
+                return step * magnitude;
+            }
+        }
+        magnitude *= 10;
+    }
+}
+
+/// Add a margin with row labels on the left and column labels on top.
+///
+/// `img` is an image of a scaled matrix with one extra row and column for the
+/// boundaries, whose first cell is `[first_row, first_col]` of the original
+/// matrix; labels show these original indices.
+/// Columns may be negative, as for the diagonals of a sheared band.
+pub(crate) fn add_axis_labels(
+    img: &RgbaImage,
+    first_row: usize,
+    first_col: isize,
+    scaling_factor: u32
+) -> RgbaImage {
+    let scaling_factor = scaling_factor.max(1);
+    let rows = (img.height().saturating_sub(1) / scaling_factor) as usize;
+    let cols = (img.width().saturating_sub(1) / scaling_factor) as usize;
+
+    let last_row = first_row + rows.saturating_sub(1);
+    let last_col = first_col + cols.saturating_sub(1) as isize;
+
+    let left_margin = text_width(&last_row.to_string()) + 2 * PADDING;
+    let top_margin = GLYPH_HEIGHT + 2 * PADDING;
+
+    let mut labelled = RgbaImage::from_pixel(img.width() + left_margin, img.height() + top_margin, WHITE);
+    imageops::replace(&mut labelled, img, left_margin, top_margin);
+
+    // column labels, centered over their column
+    let widest_col = text_width(&first_col.to_string()).max(text_width(&last_col.to_string()));
+    let col_step = tick_step(widest_col + 2 * PADDING, scaling_factor) as isize;
+    for col in (first_col..=last_col).filter(|c| c.rem_euclid(col_step) == 0) {
+        let label = col.to_string();
+        let center = left_margin + (col - first_col) as u32 * scaling_factor + scaling_factor / 2;
+        let x = center.saturating_sub(text_width(&label) / 2).max(left_margin);
+        draw_text(&mut labelled, x, PADDING, &label, BLACK);
+    }
+
+    // row labels, right aligned and centered on their row
+    let row_step = tick_step(GLYPH_HEIGHT + PADDING, scaling_factor);
+    for row in (first_row..=last_row).filter(|r| r % row_step == 0) {
+        let label = row.to_string();
+        let center = top_margin + (row - first_row) as u32 * scaling_factor + scaling_factor / 2;
+        let y = center.saturating_sub(GLYPH_HEIGHT / 2).max(top_margin);
+        draw_text(&mut labelled, left_margin - PADDING - text_width(&label), y, &label, BLACK);
+    }
+
+    labelled
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+    #[test]
+    fn test_tick_step() {
+        assert_eq!(tick_step(10, 10), 1);
+        assert_eq!(tick_step(11, 10), 2);
+        assert_eq!(tick_step(30, 1), 50);
+    }
+
+    #[test]
+    fn test_add_axis_labels() {
+        let img = RgbaImage::new(51, 31);
+        let labelled = add_axis_labels(&img, 100, 7, 10);
+
+        // "102" is the widest row label
+        assert_eq!(labelled.dimensions(), (51 + 17 + 6, 31 + 13));
+    }
+}
@@ -2,9 +2,23 @@
 pub mod colors {
     use image::Rgba;
 
-    pub const _BLACK: Rgba<u8>  = Rgba([0, 0, 0, 255]);
-    pub const _GREEN: Rgba<u8> = Rgba([0, 255, 0,  255]);
+    pub const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    pub const GREEN: Rgba<u8> = Rgba([0, 255, 0,  255]);
     pub const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
     pub const RED: Rgba<u8> = Rgba([255, 0, 0,  125]);
     pub const BLUE: Rgba<u8> = Rgba([0, 0, 255,  255]);
+
+    /// Tableau 10, a qualitative palette for categories
+    pub const QUALITATIVE: [Rgba<u8>; 10] = [
+        Rgba([78, 121, 167, 255]),
+        Rgba([242, 142, 43, 255]),
+        Rgba([225, 87, 89, 255]),
+        Rgba([118, 183, 178, 255]),
+        Rgba([89, 161, 79, 255]),
+        Rgba([237, 201, 72, 255]),
+        Rgba([176, 122, 161, 255]),
+        Rgba([255, 157, 167, 255]),
+        Rgba([156, 117, 95, 255]),
+        Rgba([186, 176, 172, 255]),
+    ];
 }
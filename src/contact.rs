@@ -0,0 +1,270 @@
+//! Chromosome contact maps
+//!
+//! Hi-C contact maps are symmetric matrices of contact counts between genomic
+//! bins, usually stored as the upper triangle in a triplet file with one
+//! `bin_i bin_j count` line per non-empty cell.
+//! [ContactMap](self::ContactMap) mirrors the triangle, normalizes counts
+//! that span orders of magnitude and draws a line at the first bin of each
+//! chromosome.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops::Range;
+
+use image::error::ImageResult;
+
+use crate::plot::CellSource;
+
+
+/// Iterative correction stops after this many rounds
+const MAX_ITERATIONS: usize = 200;
+/// or once every row sum is within this of the mean
+const TOLERANCE: f64 = 1e-5;
+
+/// How counts are normalized before plotting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// `log10(1 + count)`
+    Log,
+    /// Iterative correction (ICE), rescales rows and columns until all
+    /// covered bins have the same total contacts.
+    /// Bins without any contact are left empty.
+    Balanced,
+}
+
+/// A symmetric matrix of contacts between `nbins` bins
+#[derive(Debug, Clone)]
+pub struct ContactMap {
+    nbins: usize,
+    /// both triangles
+    contacts: HashMap<(usize, usize), f64>,
+    /// first bin of each chromosome, a line is drawn before each
+    pub boundaries: Vec<usize>,
+}
+
+impl ContactMap {
+    /// A map of `nbins` bins without any contact
+    pub fn new(nbins: usize) -> Self {
+        ContactMap { nbins, contacts: HashMap::new(), boundaries: Vec::new() }
+    }
+
+    /// Add `count` contacts between `bin_i` and `bin_j` and the mirrored cell.
+    ///
+    /// Counts for a cell given more than once are summed.
+    /// Panics if either bin is outside the map.
+    pub fn add(&mut self, bin_i: usize, bin_j: usize, count: f64) {
+        if bin_i >= self.nbins || bin_j >= self.nbins {
+            panic!(
+                "[ndarray-to-img::ContactMap::add] contact [{}, {}] is outside a map of {} bins",
+                bin_i, bin_j, self.nbins
+            );
+        }
+
+        *self.contacts.entry((bin_i, bin_j)).or_insert(0.0) += count;
+        if bin_i != bin_j {
+            *self.contacts.entry((bin_j, bin_i)).or_insert(0.0) += count;
+        }
+    }
+
+    /// Read a whitespace separated `bin_i bin_j count` triplet file.
+    ///
+    /// Empty lines and lines starting with `#` are skipped. The map has as
+    /// many bins as needed to hold the largest bin in the file.
+    pub fn read_triplets(path: &str) -> ImageResult<Self> {
+        let text = fs::read_to_string(path)?;
+
+        let invalid = |line_number: usize, line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("[ndarray-to-img::ContactMap::read_triplets] line {}: expected bin_i bin_j count, found {:?}", line_number, line)
+            )
+        };
+
+        let mut triplets: Vec<(usize, usize, f64)> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let triplet = match fields[..] {
+                [bin_i, bin_j, count] => (bin_i.parse(), bin_j.parse(), count.parse()),
+                _ => return Err(invalid(i + 1, line).into()),
+            };
+            match triplet {
+                (Ok(bin_i), Ok(bin_j), Ok(count)) => triplets.push((bin_i, bin_j, count)),
+                _ => return Err(invalid(i + 1, line).into()),
+            }
+        }
+
+        let nbins = triplets.iter().map(|&(i, j, _)| i.max(j) + 1).max().unwrap_or(0);
+        let mut map = ContactMap::new(nbins);
+        for (bin_i, bin_j, count) in triplets {
+            map.add(bin_i, bin_j, count);
+        }
+
+        Ok(map)
+    }
+
+    /// The map with a line drawn before each of the given bins
+    pub fn with_boundaries(self, boundaries: Vec<usize>) -> Self {
+        ContactMap { boundaries, ..self }
+    }
+
+    /// Number of bins along each side
+    pub fn nbins(&self) -> usize {
+        self.nbins
+    }
+
+    /// A normalized copy of the map
+    pub fn normalize(&self, normalization: Normalization) -> ContactMap {
+        let contacts = match normalization {
+            Normalization::Log => self
+                .contacts
+                .iter()
+                .map(|(&cell, &count)| (cell, (1.0 + count).log10()))
+                .collect(),
+            Normalization::Balanced => balance(self.nbins, &self.contacts),
+        };
+
+        ContactMap { contacts, ..self.clone() }
+    }
+}
+
+/// Iterative correction of a symmetric matrix, see
+/// Imakaev et al. (2012) doi:10.1038/nmeth.2148
+fn balance(nbins: usize, contacts: &HashMap<(usize, usize), f64>) -> HashMap<(usize, usize), f64> {
+    let mut balanced = contacts.clone();
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut sums = vec![0.0; nbins];
+        for (&(row, _), &count) in &balanced {
+            sums[row] += count;
+        }
+
+        let covered: Vec<f64> = sums.iter().copied().filter(|&s| s > 0.0).collect();
+        if covered.is_empty() {
+            break;
+        }
+        let mean = covered.iter().sum::<f64>() / covered.len() as f64;
+
+        let biases: Vec<f64> = sums.iter().map(|&s| if s > 0.0 { s / mean } else { 1.0 }).collect();
+        if biases.iter().all(|b| (b - 1.0).abs() < TOLERANCE) {
+            break;
+        }
+
+        for (&(row, col), count) in balanced.iter_mut() {
+            *count /= biases[row] * biases[col];
+        }
+    }
+
+    // bins without contacts stay empty
+    balanced.retain(|_, count| *count > 0.0);
+    balanced
+}
+
+impl CellSource<f64> for ContactMap {
+    fn dim(&self) -> (usize, usize) {
+        (self.nbins, self.nbins)
+    }
+
+    fn value_at(&self, row: usize, col: usize) -> Option<f64> {
+        self.contacts.get(&(row, col)).copied()
+    }
+
+    /// Only looks at the bins with contacts
+    fn max_and_min_in(&self, rows: Range<usize>, cols: Range<usize>) -> (f64, f64) {
+        let mut counts = self
+            .contacts
+            .iter()
+            .filter(|(&(row, col), _)| rows.contains(&row) && cols.contains(&col))
+            .map(|(_, &count)| count);
+
+        let first = match counts.next() {
+            Some(count) => count,
+            None => return (0.0, 0.0),
+        };
+
+        counts.fold((first, first), |(min, max), count| (min.min(count), max.max(count)))
+    }
+
+    fn boundaries(&self) -> Vec<usize> {
+        self.boundaries.clone()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::constants::colors::*;
+    use crate::plot::Plottable;
+    use crate::tests_prelude;
+
+    #[test]
+    fn test_read_triplets() {
+        let path = "test_contacts.txt";
+        fs::write(path, "# bin_i bin_j count\n0 0 10\n0 2 100\n\n1 2 1000\n").unwrap();
+
+        let map = ContactMap::read_triplets(path).unwrap();
+        assert_eq!(map.nbins(), 3);
+        assert_eq!(map.value_at(2, 0), Some(100.0));
+        assert_eq!(map.value_at(0, 2), Some(100.0));
+        assert_eq!(map.value_at(1, 1), None);
+
+        let log = map.normalize(Normalization::Log);
+        assert!((log.value_at(2, 1).unwrap() - 1001_f64.log10()).abs() < 1e-9);
+
+        fs::write(path, "0 1\n").unwrap();
+        assert!(ContactMap::read_triplets(path).is_err());
+
+        if tests_prelude::CLEANUP_TESTS {
+            assert_eq!(std::fs::remove_file(path).unwrap(), ());
+        }
+    }
+
+    #[test]
+    fn test_balance() {
+        let mut map = ContactMap::new(4);
+        map.add(0, 0, 50.0);
+        map.add(0, 1, 5.0);
+        map.add(1, 2, 20.0);
+        map.add(2, 2, 1.0);
+
+        let balanced = map.normalize(Normalization::Balanced);
+        let sums: Vec<f64> = (0..3)
+            .map(|row| (0..4).filter_map(|col| balanced.value_at(row, col)).sum())
+            .collect();
+
+        assert!(sums.iter().all(|s| (s - sums[0]).abs() < 1e-3));
+        assert!((balanced.value_at(1, 0).unwrap() - balanced.value_at(0, 1).unwrap()).abs() < 1e-9);
+        assert_eq!(balanced.value_at(3, 3), None);
+    }
+
+    #[test]
+    fn test_plot_contact_map() {
+		let config = tests_prelude::config();
+
+        let mut map = ContactMap::new(20);
+        for i in 0..20 {
+            for j in i..20 {
+                map.add(i, j, 1000.0 / (1 + j - i).pow(2) as f64);
+            }
+        }
+        let map = map.normalize(Normalization::Log).with_boundaries(vec![8, 15]);
+
+        let scaled = map.scaled(&config);
+		let img = scaled.render(&config);
+        assert_eq!(*img.get_pixel(80, 5), GREEN);
+        assert_eq!(*img.get_pixel(5, 150), GREEN);
+
+		let image_name = "test_contact_map.png";
+        assert_eq!(scaled.plot(&config, image_name).unwrap(), ());
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+}
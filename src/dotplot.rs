@@ -0,0 +1,217 @@
+//! Sequence dot plots
+//!
+//! A dot plot of a query against a target sequence marks every cell
+//! `[query_position, target_position]` covered by a match of at least `k`
+//! bases. Forward matches run down the diagonals, matches of the query against
+//! the reverse complement of the target run up the anti-diagonals.
+//!
+//! Cells hold the length of the match covering them, positive for forward
+//! matches and negative for reverse complement matches, so forward matches
+//! are drawn in red and reverse complement matches in black, longer matches
+//! darker.
+
+use std::collections::{HashMap, HashSet};
+
+use image::error::ImageResult;
+
+use crate::plot::Plottable;
+use crate::sparse::SparseMatrix;
+use crate::types::Config;
+
+
+/// Configuration for the dot plot
+#[derive(Debug, Clone)]
+pub struct DotPlotConfig {
+    /// Length of the exact k-mer matches seeding each match
+    pub kmer_size: usize,
+    /// Also look for matches against the reverse complement of the target
+    pub reverse_complement: bool,
+    /// Drop matches, made of overlapping k-mer matches along a diagonal,
+    /// shorter than this many bases
+    pub min_match_len: usize,
+}
+
+impl Default for DotPlotConfig {
+    fn default() -> Self {
+        DotPlotConfig { kmer_size: 10, reverse_complement: true, min_match_len: 0 }
+    }
+}
+
+/// Complement of a nucleotide, anything but `ACGT` is left as is
+fn complement(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' => b'A',
+        b => b,
+    }
+}
+
+/// `[query_start, target_start]` of every k-mer of the query equal to a k-mer
+/// of `target`, in order
+fn kmer_matches(query: &[u8], target: &[u8], k: usize) -> Vec<(usize, usize)> {
+    let mut index: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (j, kmer) in target.windows(k).enumerate() {
+        index.entry(kmer).or_default().push(j);
+    }
+
+    let mut matches = Vec::new();
+    for (i, kmer) in query.windows(k).enumerate() {
+        if let Some(positions) = index.get(kmer) {
+            matches.extend(positions.iter().map(|&j| (i, j)));
+        }
+    }
+
+    matches
+}
+
+/// Join k-mer matches `step` apart into runs and call `mark` with the first
+/// match and the number of bases of each run
+fn runs<S, F>(matches: &[(usize, usize)], step: S, k: usize, mut mark: F)
+where
+    S: Fn((usize, usize)) -> Option<(usize, usize)>,
+    F: FnMut((usize, usize), usize)
+{
+    let found: HashSet<(usize, usize)> = matches.iter().copied().collect();
+    let mut seen: HashSet<(usize, usize)> = HashSet::new();
+
+    for &start in matches {
+        if seen.contains(&start) {
+            continue;
+        }
+
+        let mut len = k;
+        let mut current = start;
+        seen.insert(start);
+        while let Some(next) = step(current).filter(|next| found.contains(next)) {
+            seen.insert(next);
+            current = next;
+            len += 1;
+        }
+
+        mark(start, len);
+    }
+}
+
+/// Build the sparse match matrix of `query` (rows) against `target` (cols).
+///
+/// Bases are compared case insensitively. A cell covered by both a forward and
+/// a reverse complement match keeps the longer one, the forward match on ties.
+///
+/// Panics if the `kmer_size` is 0.
+pub fn dot_matrix(query: &[u8], target: &[u8], dot_config: &DotPlotConfig) -> SparseMatrix<i32> {
+    let k = dot_config.kmer_size;
+    if k == 0 {
+        panic!("[ndarray-to-img::dot_matrix] kmer_size must be at least 1");
+    }
+
+    let query = query.to_ascii_uppercase();
+    let target = target.to_ascii_uppercase();
+
+    let mut cells: HashMap<(usize, usize), i32> = HashMap::new();
+    let mut mark = |cell: (usize, usize), value: i32| {
+        let current = cells.entry(cell).or_insert(value);
+        if value.abs() > current.abs() {
+            *current = value;
+        }
+    };
+
+    // forward matches go down the diagonal
+    let forward = kmer_matches(&query, &target, k);
+    runs(&forward, |(i, j)| Some((i + 1, j + 1)), k, |(i, j), len| {
+        if len >= dot_config.min_match_len {
+            (0..len).for_each(|t| mark((i + t, j + t), len as i32));
+        }
+    });
+
+    // a k-mer of the query at i matching the reverse complement of the target
+    // at j pairs query base i + t with target base j + k - 1 - t
+    if dot_config.reverse_complement {
+        let reverse: Vec<u8> = target.iter().rev().map(|&b| complement(b)).collect();
+        let n = target.len();
+        let backward: Vec<(usize, usize)> = kmer_matches(&query, &reverse, k)
+            .into_iter()
+            .map(|(i, j)| (i, n - k - j))
+            .collect();
+
+        runs(&backward, |(i, j)| j.checked_sub(1).map(|j| (i + 1, j)), k, |(i, j), len| {
+            if len >= dot_config.min_match_len {
+                (0..len).for_each(|t| mark((i + t, j + k - 1 - t), -(len as i32)));
+            }
+        });
+    }
+
+    let mut matrix = SparseMatrix::new(query.len(), target.len());
+    let mut entries: Vec<_> = cells.into_iter().collect();
+    entries.sort_unstable();
+    for ((row, col), value) in entries {
+        matrix.push(row, col, value);
+    }
+
+    matrix
+}
+
+/// Plot the dot plot of `query` (rows) against `target` (cols).
+///
+/// The matrix is scaled by the `scaling_factor` in [Config](crate::Config).
+pub fn dot_plot(
+    query: &[u8],
+    target: &[u8],
+    dot_config: &DotPlotConfig,
+    config: &Config,
+    output_image_path: &str
+) -> ImageResult<()> {
+    if config.verbosity > 1 {
+        eprintln!("k-mer size: {}", dot_config.kmer_size);
+    }
+
+    let matrix = dot_matrix(query, target, dot_config);
+    matrix.scaled(config).plot(config, output_image_path)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::tests_prelude;
+
+    fn value_at(matrix: &SparseMatrix<i32>, row: usize, col: usize) -> Option<i32> {
+        matrix.entries.iter().find(|e| (e.0, e.1) == (row, col)).map(|e| e.2)
+    }
+
+    #[test]
+    fn test_dot_matrix() {
+        let dot_config = DotPlotConfig { kmer_size: 3, reverse_complement: true, min_match_len: 0 };
+
+        // AACG is the reverse complement of cgtt
+        let matrix = dot_matrix(b"GGAACGTT", b"tcgttaaa", &dot_config);
+        assert_eq!(matrix.dim(), (8, 8));
+
+        // forward CGTT, query 4..8 against target 1..5
+        assert_eq!(value_at(&matrix, 4, 1), Some(4));
+        assert_eq!(value_at(&matrix, 7, 4), Some(4));
+
+        // AACG against the reverse complement of target 1..5, going up
+        assert_eq!(value_at(&matrix, 2, 4), Some(-4));
+        assert_eq!(value_at(&matrix, 5, 1), Some(-4));
+
+        // shorter matches are dropped
+        let dot_config = DotPlotConfig { min_match_len: 5, ..dot_config };
+        assert!(dot_matrix(b"GGAACGTT", b"tcgttaaa", &dot_config).entries.is_empty());
+    }
+
+    #[test]
+    fn test_dot_plot() {
+		let config = tests_prelude::config();
+        let dot_config = DotPlotConfig { kmer_size: 4, ..DotPlotConfig::default() };
+
+		let image_name = "test_dot_plot.png";
+        let sequence = b"ACGTTGCAAGGCTTACGATCGGATCCAGT";
+        assert_eq!(dot_plot(sequence, sequence, &dot_config, &config, image_name).unwrap(), ());
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+}
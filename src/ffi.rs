@@ -0,0 +1,571 @@
+//! C API
+//!
+//! Plot [Cell](crate::Cell)s with a [FfiConfig](self::FfiConfig) to a PNG
+//! file or to an in-memory PNG buffer, either all at once with
+//! [plot_cells](self::plot_cells) or through a matrix handle from
+//! [matrix_new](self::matrix_new) filled in while the caller computes.
+//! Every function returns
+//! [NDARRAY_TO_IMG_OK](self::NDARRAY_TO_IMG_OK) on success or one of the
+//! `NDARRAY_TO_IMG_ERR_*` codes, nothing is printed.
+//!
+//! Panics never unwind into C, they are caught and reported as
+//! [NDARRAY_TO_IMG_ERR_PANIC](self::NDARRAY_TO_IMG_ERR_PANIC).
+//! [last_error_message](self::last_error_message) describes the last error on
+//! the calling thread.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use image::{Rgba, RgbaImage};
+use libc::{c_char, c_int, size_t};
+use ndarray::{Array, Array2};
+
+use crate::constants::colors::*;
+use crate::html::encode_png;
+use crate::plot::{save_image, OptMatrix, Plottable};
+use crate::types::Config;
+use crate::Cell;
+
+
+/// Success
+pub const NDARRAY_TO_IMG_OK: c_int = 0;
+/// A required pointer was null
+pub const NDARRAY_TO_IMG_ERR_NULL_POINTER: c_int = 1;
+/// An argument was invalid, such as a cell outside the matrix, a scaling
+/// factor of 0 or a path that is not UTF-8
+pub const NDARRAY_TO_IMG_ERR_INVALID_ARGUMENT: c_int = 2;
+/// The image could not be encoded or written
+pub const NDARRAY_TO_IMG_ERR_IMAGE: c_int = 3;
+/// The library panicked, a bug
+pub const NDARRAY_TO_IMG_ERR_PANIC: c_int = 4;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// An error code with a description for [last_error_message]
+#[derive(Debug)]
+pub(crate) struct FfiError {
+    code: c_int,
+    message: String,
+}
+
+impl FfiError {
+    pub(crate) fn new<M: Into<String>>(code: c_int, message: M) -> Self {
+        FfiError { code, message: message.into() }
+    }
+}
+
+fn set_last_error(message: Option<String>) {
+    // a message can not hold a NUL
+    let message = message.map(|m| CString::new(m.replace('\0', " ")).unwrap());
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+}
+
+/// Run the body of an exported function, turning errors and panics into an
+/// error code and the [last_error_message]
+pub(crate) fn guard<F>(f: F) -> c_int
+where F: FnOnce() -> Result<(), FfiError>
+{
+    set_last_error(None);
+
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => NDARRAY_TO_IMG_OK,
+        Ok(Err(error)) => {
+            set_last_error(Some(error.message));
+            error.code
+        },
+        Err(payload) => {
+            let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+                (Some(message), _) => message.to_string(),
+                (_, Some(message)) => message.clone(),
+                _ => "unknown panic".to_string(),
+            };
+            set_last_error(Some(format!("[ndarray-to-img] panicked: {}", message)));
+            NDARRAY_TO_IMG_ERR_PANIC
+        },
+    }
+}
+
+/// Description of the last error on the calling thread, or null if the last
+/// call succeeded.
+///
+/// The string belongs to the library and is valid until the next call into
+/// the library on the same thread.
+#[no_mangle]
+pub extern "C" fn last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Configuration for the output image
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FfiConfig {
+    pub verbosity: u8,
+    pub with_color: bool,
+    pub annotate_image: bool,
+    pub draw_diagonal: bool,
+    pub draw_boundaries: bool,
+    pub scaling_factor: u8,
+    /// Draw each cell in its `color` instead of coloring it by its `value`
+    pub use_cell_colors: bool,
+}
+
+impl FfiConfig {
+    fn to_config(self) -> Config {
+        Config {
+            verbosity: self.verbosity,
+            with_color: self.with_color,
+            annotate_image: self.annotate_image,
+            draw_diagonal: self.draw_diagonal,
+            draw_boundaries: self.draw_boundaries,
+            scaling_factor: self.scaling_factor,
+            ..Default::default()
+        }
+    }
+}
+
+/// The `length` cells at `data`, checking that they fit in an `nrow` x `ncol`
+/// matrix
+pub(crate) unsafe fn cells_from_raw<'a>(
+    data: *const Cell,
+    length: size_t,
+    nrow: size_t,
+    ncol: size_t
+) -> Result<&'a [Cell], FfiError> {
+    if length == 0 {
+        return Ok(&[]);
+    }
+    if data.is_null() {
+        return Err(FfiError::new(NDARRAY_TO_IMG_ERR_NULL_POINTER, "[ndarray-to-img] cells are null"));
+    }
+
+    let cells = slice::from_raw_parts(data, length);
+    for cell in cells {
+        let (row, col) = (cell.position.x as usize, cell.position.y as usize);
+        if row >= nrow || col >= ncol {
+            return Err(FfiError::new(
+                NDARRAY_TO_IMG_ERR_INVALID_ARGUMENT,
+                format!("[ndarray-to-img] cell [{}, {}] is outside a {}x{} matrix", row, col, nrow, ncol)
+            ));
+        }
+    }
+
+    Ok(cells)
+}
+
+/// A matrix filled in cell by cell from C before it is plotted, see
+/// `matrix_new()`
+#[derive(Debug)]
+pub struct FfiMatrix {
+    values: Array2<Option<i32>>,
+    colors: Array2<Rgba<u8>>,
+    config: FfiConfig,
+}
+
+impl FfiMatrix {
+    pub(crate) fn new(nrow: usize, ncol: usize, config: FfiConfig) -> Self {
+        FfiMatrix {
+            values: Array::from_elem((nrow, ncol), None),
+            colors: Array::from_elem((nrow, ncol), WHITE),
+            config,
+        }
+    }
+
+    /// A matrix of `cells` already checked to be in it
+    pub(crate) fn from_cells(cells: &[Cell], nrow: usize, ncol: usize, config: FfiConfig) -> Self {
+        let mut matrix = FfiMatrix::new(nrow, ncol, config);
+        for cell in cells {
+            matrix.set(cell);
+        }
+        matrix
+    }
+
+    /// Set a cell already checked to be in the matrix
+    fn set(&mut self, cell: &Cell) {
+        let (row, col) = (cell.position.x as usize, cell.position.y as usize);
+        let c = &cell.color;
+        self.values[[row, col]] = Some(cell.value);
+        self.colors[[row, col]] = Rgba([c.red, c.green, c.blue, c.alpha]);
+    }
+
+    fn render(&self) -> RgbaImage {
+        let config = self.config.to_config();
+        let matrix = OptMatrix { matrix: self.values.view() };
+
+        if self.config.use_cell_colors {
+            matrix.render_with(&config, |row, col, _| self.colors[[row, col]])
+        } else {
+            matrix.scaled(&config).render(&config)
+        }
+    }
+
+    /// Save the image of [render](FfiMatrix::render) as a PNG file
+    pub(crate) fn plot(&self, output_path: &str) -> Result<(), FfiError> {
+        save_image(self.render(), &self.config.to_config(), output_path).map_err(image_error)
+    }
+}
+
+/// Settings of a new [FfiMatrix] until [matrix_set_config] is called
+const DEFAULT_CONFIG: FfiConfig = FfiConfig {
+    verbosity: 0,
+    with_color: true,
+    annotate_image: true,
+    draw_diagonal: true,
+    draw_boundaries: true,
+    scaling_factor: 10,
+    use_cell_colors: false,
+};
+
+/// The configuration at `config`, checking that it can be plotted
+unsafe fn read_config(config: *const FfiConfig) -> Result<FfiConfig, FfiError> {
+    if config.is_null() {
+        return Err(FfiError::new(NDARRAY_TO_IMG_ERR_NULL_POINTER, "[ndarray-to-img] config is null"));
+    }
+    let config = *config;
+    if config.scaling_factor == 0 {
+        return Err(FfiError::new(NDARRAY_TO_IMG_ERR_INVALID_ARGUMENT, "[ndarray-to-img] scaling factor is 0"));
+    }
+
+    Ok(config)
+}
+
+unsafe fn matrix_from_raw<'a>(matrix: *mut FfiMatrix) -> Result<&'a mut FfiMatrix, FfiError> {
+    matrix
+        .as_mut()
+        .ok_or_else(|| FfiError::new(NDARRAY_TO_IMG_ERR_NULL_POINTER, "[ndarray-to-img] matrix is null"))
+}
+
+/// A matrix of the `length` cells at `data` in an `nrow` x `ncol` matrix
+unsafe fn matrix_from_cells(
+    data: *const Cell,
+    length: size_t,
+    nrow: size_t,
+    ncol: size_t,
+    config: *const FfiConfig
+) -> Result<FfiMatrix, FfiError> {
+    let config = read_config(config)?;
+    let cells = cells_from_raw(data, length, nrow, ncol)?;
+
+    Ok(FfiMatrix::from_cells(cells, nrow, ncol, config))
+}
+
+/// The NUL terminated `output_path`
+unsafe fn path_from_raw<'a>(output_path: *const c_char) -> Result<&'a str, FfiError> {
+    if output_path.is_null() {
+        return Err(FfiError::new(NDARRAY_TO_IMG_ERR_NULL_POINTER, "[ndarray-to-img] output path is null"));
+    }
+    CStr::from_ptr(output_path).to_str().map_err(|_| {
+        FfiError::new(NDARRAY_TO_IMG_ERR_INVALID_ARGUMENT, "[ndarray-to-img] output path is not UTF-8")
+    })
+}
+
+pub(crate) fn image_error(e: image::ImageError) -> FfiError {
+    FfiError::new(NDARRAY_TO_IMG_ERR_IMAGE, e.to_string())
+}
+
+/// Check and reset the output buffer before rendering to it
+unsafe fn clear_buffer(output_data: *mut *mut u8, output_length: *mut size_t) -> Result<(), FfiError> {
+    if output_data.is_null() || output_length.is_null() {
+        return Err(FfiError::new(NDARRAY_TO_IMG_ERR_NULL_POINTER, "[ndarray-to-img] output buffer is null"));
+    }
+    *output_data = ptr::null_mut();
+    *output_length = 0;
+
+    Ok(())
+}
+
+/// Encode `img` as a PNG into a buffer for [free_buffer]
+unsafe fn write_buffer(img: &RgbaImage, output_data: *mut *mut u8, output_length: *mut size_t) -> Result<(), FfiError> {
+    let png = encode_png(img).map_err(image_error)?.into_boxed_slice();
+
+    *output_length = png.len();
+    *output_data = Box::into_raw(png) as *mut u8;
+
+    Ok(())
+}
+
+/// Plot `length` cells at `data` in an `nrow` x `ncol` matrix to the PNG file
+/// at `output_path`.
+///
+/// # Safety
+/// `data` must point to `length` valid, initialized `cell_t`s, `config` to a
+/// valid `ffi_config_t` and `output_path` to a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn plot_cells(
+    data: *const Cell,
+    length: size_t,
+    nrow: size_t,
+    ncol: size_t,
+    config: *const FfiConfig,
+    output_path: *const c_char
+) -> c_int {
+    guard(|| {
+        let output_path = path_from_raw(output_path)?;
+        matrix_from_cells(data, length, nrow, ncol, config)?.plot(output_path)
+    })
+}
+
+/// Plot `length` cells at `data` in an `nrow` x `ncol` matrix to an encoded
+/// PNG buffer.
+///
+/// On success `*output_data` points to `*output_length` bytes that must be
+/// released with `free_buffer()`, otherwise they are set to null and 0.
+///
+/// # Safety
+/// `data` must point to `length` valid, initialized `cell_t`s, `config` to a
+/// valid `ffi_config_t`, and `output_data` and `output_length` must be valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn plot_cells_to_buffer(
+    data: *const Cell,
+    length: size_t,
+    nrow: size_t,
+    ncol: size_t,
+    config: *const FfiConfig,
+    output_data: *mut *mut u8,
+    output_length: *mut size_t
+) -> c_int {
+    guard(|| {
+        clear_buffer(output_data, output_length)?;
+        let img = matrix_from_cells(data, length, nrow, ncol, config)?.render();
+        write_buffer(&img, output_data, output_length)
+    })
+}
+
+/// Release a buffer from `plot_cells_to_buffer()`, null is ignored.
+///
+/// # Safety
+/// `data` and `length` must be exactly as returned by
+/// `plot_cells_to_buffer()` and the buffer must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn free_buffer(data: *mut u8, length: size_t) {
+    if data.is_null() {
+        return;
+    }
+    guard(|| {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, length)));
+        Ok(())
+    });
+}
+
+/// Create an empty `nrow` x `ncol` matrix at `*matrix` to fill in with
+/// `matrix_set_cell()` or `matrix_set_cells()` and plot with `matrix_plot()` or
+/// `matrix_plot_to_buffer()`.
+///
+/// Cells are colored by their values, scaled 10 times, with annotations and
+/// without printing anything until `matrix_set_config()` is called.
+/// The matrix must be released with `matrix_free()`.
+///
+/// # Safety
+/// `matrix` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_new(nrow: size_t, ncol: size_t, matrix: *mut *mut FfiMatrix) -> c_int {
+    guard(|| {
+        if matrix.is_null() {
+            return Err(FfiError::new(NDARRAY_TO_IMG_ERR_NULL_POINTER, "[ndarray-to-img] matrix is null"));
+        }
+        *matrix = ptr::null_mut();
+        *matrix = Box::into_raw(Box::new(FfiMatrix::new(nrow, ncol, DEFAULT_CONFIG)));
+
+        Ok(())
+    })
+}
+
+/// Set the value and color of a cell, replacing any previous one.
+///
+/// # Safety
+/// `matrix` must come from `matrix_new()`.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_set_cell(matrix: *mut FfiMatrix, cell: Cell) -> c_int {
+    matrix_set_cells(matrix, &cell, 1)
+}
+
+/// Set the `length` cells at `data`, either all of them or, if one is
+/// outside the matrix, none.
+///
+/// # Safety
+/// `matrix` must come from `matrix_new()` and `data` must point to `length`
+/// valid, initialized `cell_t`s.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_set_cells(matrix: *mut FfiMatrix, data: *const Cell, length: size_t) -> c_int {
+    guard(|| {
+        let matrix = matrix_from_raw(matrix)?;
+        let (nrow, ncol) = matrix.values.dim();
+
+        for cell in cells_from_raw(data, length, nrow, ncol)? {
+            matrix.set(cell);
+        }
+
+        Ok(())
+    })
+}
+
+/// Use `config` for the next plots of the matrix.
+///
+/// # Safety
+/// `matrix` must come from `matrix_new()` and `config` must point to a valid
+/// `ffi_config_t`.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_set_config(matrix: *mut FfiMatrix, config: *const FfiConfig) -> c_int {
+    guard(|| {
+        let matrix = matrix_from_raw(matrix)?;
+        matrix.config = read_config(config)?;
+
+        Ok(())
+    })
+}
+
+/// Plot the matrix to the PNG file at `output_path`, the matrix can still be
+/// changed and plotted again afterwards.
+///
+/// # Safety
+/// `matrix` must come from `matrix_new()` and `output_path` must be a NUL
+/// terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_plot(matrix: *mut FfiMatrix, output_path: *const c_char) -> c_int {
+    guard(|| {
+        let matrix = matrix_from_raw(matrix)?;
+        matrix.plot(path_from_raw(output_path)?)
+    })
+}
+
+/// Plot the matrix to an encoded PNG buffer like `plot_cells_to_buffer()`.
+///
+/// # Safety
+/// `matrix` must come from `matrix_new()`, and `output_data` and
+/// `output_length` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_plot_to_buffer(
+    matrix: *mut FfiMatrix,
+    output_data: *mut *mut u8,
+    output_length: *mut size_t
+) -> c_int {
+    guard(|| {
+        clear_buffer(output_data, output_length)?;
+        let matrix = matrix_from_raw(matrix)?;
+        write_buffer(&matrix.render(), output_data, output_length)
+    })
+}
+
+/// Release a matrix from `matrix_new()`, null is ignored.
+///
+/// # Safety
+/// `matrix` must come from `matrix_new()` and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn matrix_free(matrix: *mut FfiMatrix) {
+    if matrix.is_null() {
+        return;
+    }
+    guard(|| {
+        drop(Box::from_raw(matrix));
+        Ok(())
+    });
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::{Color, Position};
+
+    const CONFIG: FfiConfig = FfiConfig {
+        verbosity: 0,
+        with_color: true,
+        annotate_image: true,
+        draw_diagonal: true,
+        draw_boundaries: true,
+        scaling_factor: 10,
+        use_cell_colors: true,
+    };
+
+    fn cell(x: u32, y: u32, value: i32) -> Cell {
+        Cell {
+            position: Position { x, y },
+            value,
+            color: Color { red: 0, green: 0, blue: 255, alpha: 255 },
+        }
+    }
+
+    #[test]
+    fn test_plot_cells_to_buffer() {
+        let cells = [cell(1, 2, 10), cell(8, 9, -190)];
+        let (mut data, mut length) = (ptr::null_mut(), 0);
+
+        let code = unsafe {
+            plot_cells_to_buffer(cells.as_ptr(), cells.len(), 10, 10, &CONFIG, &mut data, &mut length)
+        };
+        assert_eq!(code, NDARRAY_TO_IMG_OK);
+
+        let png = unsafe { slice::from_raw_parts(data, length) };
+        let img = image::load_from_memory(png).unwrap().to_rgba8();
+        assert_eq!(*img.get_pixel(25, 15), BLUE);
+
+        unsafe { free_buffer(data, length) };
+    }
+
+    #[test]
+    fn test_plot_cells_errors() {
+        let cells = [cell(10, 2, 10)];
+        let path = c"test_ffi_image.png".as_ptr();
+
+        let code = unsafe { plot_cells(cells.as_ptr(), cells.len(), 10, 10, &CONFIG, path) };
+        assert_eq!(code, NDARRAY_TO_IMG_ERR_INVALID_ARGUMENT);
+        let message = unsafe { CStr::from_ptr(last_error_message()) };
+        assert_eq!(message.to_str().unwrap(), "[ndarray-to-img] cell [10, 2] is outside a 10x10 matrix");
+
+        let code = unsafe { plot_cells(cells.as_ptr(), cells.len(), 10, 10, ptr::null(), path) };
+        assert_eq!(code, NDARRAY_TO_IMG_ERR_NULL_POINTER);
+
+        let config = FfiConfig { scaling_factor: 0, ..CONFIG };
+        let code = unsafe { plot_cells(cells.as_ptr(), cells.len(), 11, 10, &config, path) };
+        assert_eq!(code, NDARRAY_TO_IMG_ERR_INVALID_ARGUMENT);
+    }
+
+    #[test]
+    fn test_guard() {
+        assert_eq!(guard(|| Ok(())), NDARRAY_TO_IMG_OK);
+        assert!(last_error_message().is_null());
+
+        assert_eq!(guard(|| panic!("oops")), NDARRAY_TO_IMG_ERR_PANIC);
+        let message = unsafe { CStr::from_ptr(last_error_message()) };
+        assert_eq!(message.to_str().unwrap(), "[ndarray-to-img] panicked: oops");
+    }
+
+    #[test]
+    fn test_matrix_handle() {
+        let mut matrix = ptr::null_mut();
+        assert_eq!(unsafe { matrix_new(10, 10, &mut matrix) }, NDARRAY_TO_IMG_OK);
+
+        assert_eq!(unsafe { matrix_set_cell(matrix, cell(1, 2, 10)) }, NDARRAY_TO_IMG_OK);
+        let cells = [cell(8, 9, -190), cell(10, 2, 10)];
+        assert_eq!(unsafe { matrix_set_cells(matrix, cells.as_ptr(), 2) }, NDARRAY_TO_IMG_ERR_INVALID_ARGUMENT);
+        assert_eq!(unsafe { matrix_set_cells(matrix, cells.as_ptr(), 1) }, NDARRAY_TO_IMG_OK);
+        assert_eq!(unsafe { matrix_set_config(matrix, &CONFIG) }, NDARRAY_TO_IMG_OK);
+
+        let (mut data, mut length) = (ptr::null_mut(), 0);
+        assert_eq!(unsafe { matrix_plot_to_buffer(matrix, &mut data, &mut length) }, NDARRAY_TO_IMG_OK);
+        let png = unsafe { slice::from_raw_parts(data, length) };
+        let img = image::load_from_memory(png).unwrap().to_rgba8();
+        assert_eq!(*img.get_pixel(25, 15), BLUE);
+        assert_eq!(*img.get_pixel(95, 85), BLUE);
+        unsafe { free_buffer(data, length) };
+
+        let image_name = "test_ffi_matrix.png";
+        let path = c"test_ffi_matrix.png".as_ptr();
+        assert_eq!(unsafe { matrix_plot(matrix, path) }, NDARRAY_TO_IMG_OK);
+        assert_eq!(unsafe { matrix_plot(ptr::null_mut(), path) }, NDARRAY_TO_IMG_ERR_NULL_POINTER);
+
+        unsafe { matrix_free(matrix) };
+
+        if crate::tests_prelude::CLEANUP_TESTS {
+            assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Interactive HTML output
+//!
+//! Writes a single self-contained HTML file holding the rendered image
+//! (as a base64 PNG) and the cell values of the matrix.
+//! Hovering over the image shows the row, column and value of the cell under
+//! the cursor, the mouse wheel zooms and dragging pans.
+//! No external scripts, stylesheets or images are referenced.
+
+use std::fs;
+use std::fmt::Write;
+
+use image::ColorType;
+use image::codecs::png::PngEncoder;
+use image::error::ImageResult;
+use num::cast::ToPrimitive;
+
+use crate::plot::Plottable;
+use crate::types::{Band, Config};
+
+
+/// Encode an image as PNG in memory
+pub(crate) fn encode_png(img: &image::RgbaImage) -> ImageResult<Vec<u8>> {
+    let mut buffer: Vec<u8> = Vec::new();
+    PngEncoder::new(&mut buffer).encode(img, img.width(), img.height(), ColorType::Rgba8)?;
+    Ok(buffer)
+}
+
+/// Cell values as a JSON array of rows, empty cells are `null`
+fn cells_to_json<T, P>(matrix: &P) -> String
+where
+    T: ToPrimitive,
+    P: Plottable<T>
+{
+    let (rows, cols) = matrix.dim();
+    let mut json = String::with_capacity(rows * cols * 4);
+
+    json.push('[');
+    for row in 0..rows {
+        if row > 0 { json.push(','); }
+        json.push('[');
+        for col in 0..cols {
+            if col > 0 { json.push(','); }
+            match matrix.value_at(row, col).and_then(|v| v.to_f64()) {
+                Some(v) if v.is_finite() => { write!(json, "{}", v).unwrap(); },
+                _ => json.push_str("null"),
+            }
+        }
+        json.push(']');
+    }
+    json.push(']');
+
+    json
+}
+
+/// Generate an interactive HTML page from an **unscaled** matrix.
+///
+/// The matrix is scaled by the `scaling_factor` in [Config](crate::Config)
+/// before rendering so that tooltips can map pixels back to cells.
+/// The whole matrix is rendered, use pan and zoom instead of a `viewport`,
+/// and bands are not sheared.
+pub fn write_html<T, P>(matrix: &P, config: &Config, output_html_path: &str) -> ImageResult<()>
+where
+    T: ToPrimitive + num::Zero + Copy + PartialOrd,
+    P: Plottable<T>
+{
+    if config.verbosity > 0 {
+        eprintln!("Generating html {}", output_html_path);
+    }
+
+    let config = &Config {
+        viewport: None,
+        band: config.band.map(|band| Band { shear: false, ..band }),
+        ..config.clone()
+    };
+
+    let (rows, cols) = matrix.dim();
+    let img = matrix.scaled(config).render(config);
+    let png = base64::encode(encode_png(&img)?);
+    let cells = cells_to_json(matrix);
+
+    let page = TEMPLATE
+        .replace("{{ROWS}}", &rows.to_string())
+        .replace("{{COLS}}", &cols.to_string())
+        .replace("{{SCALING_FACTOR}}", &config.scaling_factor.to_string())
+        .replace("{{CELLS}}", &cells)
+        .replace("{{PNG}}", &png);
+
+    fs::write(output_html_path, page)?;
+
+    Ok(())
+}
+
+const TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>ndarray-to-img</title>
+<style>
+  html, body { margin: 0; height: 100%; overflow: hidden; background: #eee; font-family: monospace; }
+  #viewport { width: 100%; height: 100%; cursor: grab; }
+  #viewport.dragging { cursor: grabbing; }
+  #matrix { position: absolute; transform-origin: 0 0; image-rendering: pixelated; image-rendering: crisp-edges; }
+  #tooltip { position: fixed; display: none; pointer-events: none; padding: 2px 6px;
+             background: rgba(0, 0, 0, 0.8); color: #fff; font-size: 12px; white-space: pre; }
+  #info { position: fixed; right: 8px; top: 8px; padding: 2px 6px; background: rgba(255, 255, 255, 0.8); font-size: 12px; }
+</style>
+</head>
+<body>
+<div id="viewport"><img id="matrix" draggable="false" src="data:image/png;base64,{{PNG}}"></div>
+<div id="tooltip"></div>
+<div id="info">{{ROWS}} x {{COLS}} &middot; scroll to zoom, drag to pan, double click to reset</div>
+<script>
+(function () {
+  var ROWS = {{ROWS}};
+  var COLS = {{COLS}};
+  var SCALING_FACTOR = {{SCALING_FACTOR}};
+  var CELLS = {{CELLS}};
+
+  var viewport = document.getElementById("viewport");
+  var img = document.getElementById("matrix");
+  var tooltip = document.getElementById("tooltip");
+
+  var zoom = 1, panX = 0, panY = 0;
+  var dragging = false, lastX = 0, lastY = 0;
+
+  function update() {
+    img.style.transform = "translate(" + panX + "px," + panY + "px) scale(" + zoom + ")";
+  }
+
+  function reset() {
+    zoom = 1; panX = 0; panY = 0;
+    update();
+  }
+
+  viewport.addEventListener("wheel", function (e) {
+    e.preventDefault();
+    var factor = e.deltaY < 0 ? 1.2 : 1 / 1.2;
+    // zoom about the cursor
+    panX = e.clientX - (e.clientX - panX) * factor;
+    panY = e.clientY - (e.clientY - panY) * factor;
+    zoom *= factor;
+    update();
+  }, { passive: false });
+
+  viewport.addEventListener("mousedown", function (e) {
+    dragging = true; lastX = e.clientX; lastY = e.clientY;
+    viewport.classList.add("dragging");
+  });
+
+  window.addEventListener("mouseup", function () {
+    dragging = false;
+    viewport.classList.remove("dragging");
+  });
+
+  viewport.addEventListener("dblclick", reset);
+
+  viewport.addEventListener("mousemove", function (e) {
+    if (dragging) {
+      panX += e.clientX - lastX; panY += e.clientY - lastY;
+      lastX = e.clientX; lastY = e.clientY;
+      update();
+    }
+
+    var rect = img.getBoundingClientRect();
+    var x = (e.clientX - rect.left) / rect.width * img.naturalWidth;
+    var y = (e.clientY - rect.top) / rect.height * img.naturalHeight;
+    var row = Math.floor(y / SCALING_FACTOR);
+    var col = Math.floor(x / SCALING_FACTOR);
+
+    if (x < 0 || y < 0 || row >= ROWS || col >= COLS) {
+      tooltip.style.display = "none";
+      return;
+    }
+
+    var value = CELLS[row][col];
+    tooltip.textContent = "row: " + row + "\ncol: " + col + "\nvalue: " + (value === null ? "empty" : value);
+    tooltip.style.left = (e.clientX + 12) + "px";
+    tooltip.style.top = (e.clientY + 12) + "px";
+    tooltip.style.display = "block";
+  });
+
+  viewport.addEventListener("mouseleave", function () {
+    tooltip.style.display = "none";
+  });
+
+  update();
+})();
+</script>
+</body>
+</html>
+"#;
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::plot::OptMatrix;
+    use crate::tests_prelude;
+    use ndarray::{Array, Array2};
+
+    #[test]
+    fn test_write_html() {
+		let config = tests_prelude::config();
+
+		let mut matrix: Array2<Option<i32>> = Array::from_elem((10, 10), None);
+
+		matrix[[1,2]] = Some(1);
+		matrix[[8,9]] = Some(-190);
+
+        let matrix = OptMatrix {
+            matrix,
+        };
+
+		let html_name = "test_opt_image.html";
+        assert_eq!(write_html(&matrix, &config, html_name).unwrap(), ());
+
+        let page = fs::read_to_string(html_name).unwrap();
+        assert!(page.contains("data:image/png;base64,"));
+        assert!(page.contains("[null,null,null,null,null,null,null,null,null,-190]"));
+        assert!(!page.contains("{{"));
+
+		// clean up tests
+		// let failed clean up result in error
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(html_name).unwrap(), ());
+		}
+    }
+}
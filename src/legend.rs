@@ -0,0 +1,73 @@
+//! Legends
+//!
+//! A list of categories, each a color swatch and a label, drawn in a margin
+//! to the right of an image.
+
+use image::{imageops, Rgba, RgbaImage};
+
+use crate::constants::colors::*;
+use crate::font::{draw_text, text_width, GLYPH_HEIGHT};
+
+/// Space around entries
+const PADDING: u32 = 3;
+/// Width and height of a swatch, as tall as the text
+const SWATCH: u32 = GLYPH_HEIGHT;
+
+/// Add a margin on the right listing each `(label, color)` entry.
+///
+/// The image grows taller if the legend does not fit next to it.
+pub(crate) fn add_legend(img: &RgbaImage, entries: &[(String, Rgba<u8>)]) -> RgbaImage {
+    let panel = legend_panel(entries);
+
+    let mut with_legend = RgbaImage::from_pixel(
+        img.width() + panel.width(),
+        img.height().max(panel.height()),
+        WHITE
+    );
+    imageops::replace(&mut with_legend, img, 0, 0);
+    imageops::replace(&mut with_legend, &panel, img.width(), 0);
+
+    with_legend
+}
+
+/// The margin [add_legend] puts on the right of an image, on its own
+pub(crate) fn legend_panel(entries: &[(String, Rgba<u8>)]) -> RgbaImage {
+    let widest_label = entries.iter().map(|(label, _)| text_width(label)).max().unwrap_or(0);
+    let legend_width = PADDING + SWATCH + PADDING + widest_label + PADDING;
+    let legend_height = PADDING + entries.len() as u32 * (GLYPH_HEIGHT + PADDING);
+
+    let mut panel = RgbaImage::from_pixel(legend_width, legend_height, WHITE);
+
+    let left = PADDING;
+    for (i, (label, color)) in entries.iter().enumerate() {
+        let top = PADDING + i as u32 * (GLYPH_HEIGHT + PADDING);
+
+        // outlined so light colors stand out from the background
+        for dy in 0..SWATCH {
+            for dx in 0..SWATCH {
+                let edge = dx == 0 || dy == 0 || dx == SWATCH - 1 || dy == SWATCH - 1;
+                panel.put_pixel(left + dx, top + dy, if edge { BLACK } else { *color });
+            }
+        }
+
+        draw_text(&mut panel, left + SWATCH + PADDING, top, label, BLACK);
+    }
+
+    panel
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+    #[test]
+    fn test_add_legend() {
+        let img = RgbaImage::new(51, 11);
+        let entries = vec![("M".to_string(), RED), ("I".to_string(), BLUE), ("DEL".to_string(), GREEN)];
+        let with_legend = add_legend(&img, &entries);
+
+        // "DEL" is the widest label, three entries are taller than the image
+        assert_eq!(with_legend.dimensions(), (51 + 3 + 7 + 3 + 17 + 3, 3 + 3 * 10));
+        assert_eq!(*with_legend.get_pixel(51 + 3 + 3, 3 + 10 + 3), BLUE);
+    }
+}
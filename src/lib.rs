@@ -20,12 +20,12 @@ fn example_fn() {
     // Config
     // ------
     let mut config =  ndarray_to_img::Config {
-	    verbosity: 1,
-	    with_color: true,
-	    annotate_image: true,
-	    draw_diagonal: true,
-	    draw_boundaries: true,
-	    scaling_factor: 10,
+        verbosity: 1,
+        annotate_image: true,
+        draw_diagonal: true,
+        draw_boundaries: true,
+        scaling_factor: 10,
+        ..Default::default()
     };
 
     config.scaling_factor = 50;
@@ -34,21 +34,21 @@ fn example_fn() {
     // OptMatrix<T>
     // ------------
 
-	let mut matrix: Array2<Option<i32>> = Array::from_elem((10, 10), None);
+    let mut matrix: Array2<Option<i32>> = Array::from_elem((10, 10), None);
 
-	matrix[[1,2]] = Some(1);
-	matrix[[2,5]] = Some(7);
-	matrix[[4,5]] = Some(10);
-	matrix[[5,5]] = Some(5);
-	matrix[[5,4]] = Some(-15);
-	matrix[[8,9]] = Some(-190);
+    matrix[[1,2]] = Some(1);
+    matrix[[2,5]] = Some(7);
+    matrix[[4,5]] = Some(10);
+    matrix[[5,5]] = Some(5);
+    matrix[[5,4]] = Some(-15);
+    matrix[[8,9]] = Some(-190);
 
     let matrix = plot::OptMatrix {
         matrix,
     };
 
-	let scaled_matrix: plot::OptMatrix<i32> = matrix.scale_matrix(&config);
-	let image_file_path = "test_opt_image.png";
+    let scaled_matrix: plot::OptMatrix<i32> = matrix.scale_matrix(&config);
+    let image_file_path = "test_opt_image.png";
 
     assert_eq!(scaled_matrix.plot(&config, image_file_path).unwrap(), ());
 
@@ -58,57 +58,78 @@ fn example_fn() {
 
     let mut matrix: Array2<i32> = Array::from_elem((10, 10), 0);
 
-	matrix[[1,2]] = 1;
-	matrix[[2,5]] = 7;
-	matrix[[4,5]] = 10;
-	matrix[[5,5]] = 5;
-	matrix[[5,4]] = -15;
-	matrix[[8,9]] = -190;
+    matrix[[1,2]] = 1;
+    matrix[[2,5]] = 7;
+    matrix[[4,5]] = 10;
+    matrix[[5,5]] = 5;
+    matrix[[5,4]] = -15;
+    matrix[[8,9]] = -190;
 
     let matrix = plot::Matrix {
         matrix,
     };
 
-	let scaled_matrix: plot::Matrix<i32> = matrix.scale_matrix(&config);
-	let image_name = "test_non_opt_image.png";
+    let scaled_matrix: plot::Matrix<i32> = matrix.scale_matrix(&config);
+    let image_name = "test_non_opt_image.png";
     assert_eq!(scaled_matrix.plot(&config, image_name).unwrap(), ());
 }
 ```
  */
 
 
-use libc::{c_int, __u8, __u32, size_t};
-use std::slice;
-use ndarray::{Array2, Array};
+#[cfg(not(target_arch = "wasm32"))]
+use libc::{c_int, size_t};
 
 
+mod axis;
 mod constants;
+mod font;
+mod legend;
 mod tests_prelude;
 mod types;
 pub mod plot;
+pub mod scale;
+pub mod html;
+pub mod animate;
+pub mod sparse;
+pub mod tiles;
+pub mod stream;
+pub mod wavefront;
+pub mod dotplot;
+pub mod contact;
+// libc and C strings are not available on wasm32
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 mod rusty;
 pub use rusty::*;
-pub use types::Config;
+pub use types::{Band, Categories, Clip, Config, Limits, Transform, Viewport};
 
 /// For C++ FFI
+#[cfg(not(target_arch = "wasm32"))]
 #[repr(C)]
 #[derive(Debug)]
 pub struct Position {
-		pub x: __u32,
-    pub y: __u32,
+		pub x: u32,
+    pub y: u32,
 }
 
 /// For C++ FFI
+#[cfg(not(target_arch = "wasm32"))]
 #[repr(C)]
 #[derive(Debug)]
 pub struct Color {
-		pub red: __u8,
-    pub green: __u8,
-		pub blue: __u8,
-		pub alpha: __u8
+		pub red: u8,
+    pub green: u8,
+		pub blue: u8,
+		pub alpha: u8
 }
 
 /// For C++ FFI
+#[cfg(not(target_arch = "wasm32"))]
 #[repr(C)]
 #[derive(Debug)]
 pub struct Cell {
@@ -119,44 +140,57 @@ pub struct Cell {
 
 
 /// For C++ FFI
+///
+/// Plots the cells colored by their values, see `read_colored_cells()`.
+///
+/// # Safety
+/// `data` must point to `length` valid, initialized `cell_t`s.
+#[cfg(not(target_arch = "wasm32"))]
 #[no_mangle]
-pub extern fn read_cells(
+pub unsafe extern "C" fn read_cells(
 		data : *const Cell,
 		length : size_t,
 		nrow: size_t,
 		ncol: size_t
-) {
-		let vec = unsafe{slice::from_raw_parts(data, length)};
-
-    for x in vec {
-				println !("{:?}", x);
-		}
+) -> c_int {
+		read_colored_cells(data, length, nrow, ncol, false)
+}
 
-		call_rust(vec, nrow, ncol);
+/// For C++ FFI
+///
+/// Plots the cells in the `color` of each cell if `use_cell_colors` is
+/// true, otherwise falls back to coloring them by their values.
+/// Returns an error code like the other functions, `NDARRAY_TO_IMG_OK` on
+/// success.
+///
+/// # Safety
+/// `data` must point to `length` valid, initialized `cell_t`s.
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub unsafe extern "C" fn read_colored_cells(
+		data : *const Cell,
+		length : size_t,
+		nrow: size_t,
+		ncol: size_t,
+		use_cell_colors: bool
+) -> c_int {
+		ffi::guard(|| {
+				let vec = ffi::cells_from_raw(data, length, nrow, ncol)?;
+				call_rust(vec, nrow, ncol, use_cell_colors)
+		})
 }
 
-fn call_rust(data: &[Cell], nrow: usize, ncol: usize) {
-		let config =  types::Config {
+#[cfg(not(target_arch = "wasm32"))]
+fn call_rust(data: &[Cell], nrow: usize, ncol: usize, use_cell_colors: bool) -> Result<(), ffi::FfiError> {
+		let config = ffi::FfiConfig {
 				verbosity: 1,
 				with_color: true,
 				annotate_image: true,
 				draw_diagonal: true,
 				draw_boundaries: true,
 				scaling_factor: 10,
+				use_cell_colors,
 		};
 
-
-		let dim = (nrow as usize, ncol as usize);
-    let x = ndarray::Dim(dim);
-    let mut matrix: Array2<Option<i32>> = Array::from_elem(x, None);
-
-		for d in data {
-				let x = d.position.x as usize;
-				let y = d.position.y  as usize;
-				matrix[[x, y]] = Some(d.value);
-		}
-
-		let scaled_matrix = rusty::scale_matrix(&matrix, &config);
-    let image_name = "all.png";
-    rusty::generate_image(&scaled_matrix, &config, &image_name).unwrap();
+		ffi::FfiMatrix::from_cells(data, nrow, ncol, config).plot("all.png")
 }
@@ -1,27 +1,242 @@
-#include <cstdint>
+#ifndef NDARRAY_TO_IMG_H
+#define NDARRAY_TO_IMG_H
 
-struct position_t {
+/* Generated by cbindgen from the Rust sources, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Success
+ */
+#define NDARRAY_TO_IMG_OK 0
+
+/**
+ * A required pointer was null
+ */
+#define NDARRAY_TO_IMG_ERR_NULL_POINTER 1
+
+/**
+ * An argument was invalid, such as a cell outside the matrix, a scaling
+ * factor of 0 or a path that is not UTF-8
+ */
+#define NDARRAY_TO_IMG_ERR_INVALID_ARGUMENT 2
+
+/**
+ * The image could not be encoded or written
+ */
+#define NDARRAY_TO_IMG_ERR_IMAGE 3
+
+/**
+ * The library panicked, a bug
+ */
+#define NDARRAY_TO_IMG_ERR_PANIC 4
+
+/**
+ * A matrix filled in cell by cell from C before it is plotted, see
+ * `matrix_new()`
+ */
+typedef struct matrix_t matrix_t;
+
+/**
+ * For C++ FFI
+ */
+typedef struct position_t {
   uint32_t x;
   uint32_t y;
+} position_t;
 
-  position_t(uint32_t x_pos, uint32_t y_pos) : x(x_pos), y(y_pos) {}
-};
-
-struct color_t {
+/**
+ * For C++ FFI
+ */
+typedef struct color_t {
   uint8_t red;
   uint8_t green;
   uint8_t blue;
   uint8_t alpha;
+} color_t;
 
-  color_t(uint8_t r, uint8_t g, uint8_t b, uint8_t a)
-      : red(r), green(g), blue(b), alpha(a) {}
-};
-
-struct cell_t {
-  position_t pos;
+/**
+ * For C++ FFI
+ */
+typedef struct cell_t {
+  struct position_t position;
   int value;
-  color_t color;
-};
+  struct color_t color;
+} cell_t;
+
+/**
+ * Configuration for the output image
+ */
+typedef struct ffi_config_t {
+  uint8_t verbosity;
+  bool with_color;
+  bool annotate_image;
+  bool draw_diagonal;
+  bool draw_boundaries;
+  uint8_t scaling_factor;
+  /**
+   * Draw each cell in its `color` instead of coloring it by its `value`
+   */
+  bool use_cell_colors;
+} ffi_config_t;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * For C++ FFI
+ *
+ * Plots the cells colored by their values, see `read_colored_cells()`.
+ *
+ * # Safety
+ * `data` must point to `length` valid, initialized `cell_t`s.
+ */
+int read_cells(const struct cell_t *data, size_t length, size_t nrow, size_t ncol);
+
+/**
+ * For C++ FFI
+ *
+ * Plots the cells in the `color` of each cell if `use_cell_colors` is
+ * true, otherwise falls back to coloring them by their values.
+ * Returns an error code like the other functions, `NDARRAY_TO_IMG_OK` on
+ * success.
+ *
+ * # Safety
+ * `data` must point to `length` valid, initialized `cell_t`s.
+ */
+int read_colored_cells(const struct cell_t *data,
+                       size_t length,
+                       size_t nrow,
+                       size_t ncol,
+                       bool use_cell_colors);
+
+/**
+ * Description of the last error on the calling thread, or null if the last
+ * call succeeded.
+ *
+ * The string belongs to the library and is valid until the next call into
+ * the library on the same thread.
+ */
+const char *last_error_message(void);
+
+/**
+ * Plot `length` cells at `data` in an `nrow` x `ncol` matrix to the PNG file
+ * at `output_path`.
+ *
+ * # Safety
+ * `data` must point to `length` valid, initialized `cell_t`s, `config` to a
+ * valid `ffi_config_t` and `output_path` to a NUL terminated string.
+ */
+int plot_cells(const struct cell_t *data,
+               size_t length,
+               size_t nrow,
+               size_t ncol,
+               const struct ffi_config_t *config,
+               const char *output_path);
+
+/**
+ * Plot `length` cells at `data` in an `nrow` x `ncol` matrix to an encoded
+ * PNG buffer.
+ *
+ * On success `*output_data` points to `*output_length` bytes that must be
+ * released with `free_buffer()`, otherwise they are set to null and 0.
+ *
+ * # Safety
+ * `data` must point to `length` valid, initialized `cell_t`s, `config` to a
+ * valid `ffi_config_t`, and `output_data` and `output_length` must be valid
+ * for writes.
+ */
+int plot_cells_to_buffer(const struct cell_t *data,
+                         size_t length,
+                         size_t nrow,
+                         size_t ncol,
+                         const struct ffi_config_t *config,
+                         uint8_t **output_data,
+                         size_t *output_length);
+
+/**
+ * Release a buffer from `plot_cells_to_buffer()`, null is ignored.
+ *
+ * # Safety
+ * `data` and `length` must be exactly as returned by
+ * `plot_cells_to_buffer()` and the buffer must not be used afterwards.
+ */
+void free_buffer(uint8_t *data, size_t length);
+
+/**
+ * Create an empty `nrow` x `ncol` matrix at `*matrix` to fill in with
+ * `matrix_set_cell()` or `matrix_set_cells()` and plot with `matrix_plot()` or
+ * `matrix_plot_to_buffer()`.
+ *
+ * Cells are colored by their values, scaled 10 times, with annotations and
+ * without printing anything until `matrix_set_config()` is called.
+ * The matrix must be released with `matrix_free()`.
+ *
+ * # Safety
+ * `matrix` must be valid for writes.
+ */
+int matrix_new(size_t nrow, size_t ncol, struct matrix_t **matrix);
+
+/**
+ * Set the value and color of a cell, replacing any previous one.
+ *
+ * # Safety
+ * `matrix` must come from `matrix_new()`.
+ */
+int matrix_set_cell(struct matrix_t *matrix, struct cell_t cell);
+
+/**
+ * Set the `length` cells at `data`, either all of them or, if one is
+ * outside the matrix, none.
+ *
+ * # Safety
+ * `matrix` must come from `matrix_new()` and `data` must point to `length`
+ * valid, initialized `cell_t`s.
+ */
+int matrix_set_cells(struct matrix_t *matrix, const struct cell_t *data, size_t length);
+
+/**
+ * Use `config` for the next plots of the matrix.
+ *
+ * # Safety
+ * `matrix` must come from `matrix_new()` and `config` must point to a valid
+ * `ffi_config_t`.
+ */
+int matrix_set_config(struct matrix_t *matrix, const struct ffi_config_t *config);
+
+/**
+ * Plot the matrix to the PNG file at `output_path`, the matrix can still be
+ * changed and plotted again afterwards.
+ *
+ * # Safety
+ * `matrix` must come from `matrix_new()` and `output_path` must be a NUL
+ * terminated string.
+ */
+int matrix_plot(struct matrix_t *matrix, const char *output_path);
+
+/**
+ * Plot the matrix to an encoded PNG buffer like `plot_cells_to_buffer()`.
+ *
+ * # Safety
+ * `matrix` must come from `matrix_new()`, and `output_data` and
+ * `output_length` must be valid for writes.
+ */
+int matrix_plot_to_buffer(struct matrix_t *matrix, uint8_t **output_data, size_t *output_length);
+
+/**
+ * Release a matrix from `matrix_new()`, null is ignored.
+ *
+ * # Safety
+ * `matrix` must come from `matrix_new()` and must not be used afterwards.
+ */
+void matrix_free(struct matrix_t *matrix);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
 
-extern "C" void read_cells(cell_t const *cell, std::size_t length,
-                           std::size_t nrow, std::size_t ncol);
+#endif  /* NDARRAY_TO_IMG_H */
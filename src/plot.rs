@@ -11,378 +11,669 @@
 //!    - 0 is white
 //!    - positive_number is a shade of red
 //!    - negative_number is a shade of grey
+//!
+//! Other matrix types, such as sparse ones or ones computed on the fly, are
+//! plotted like an [OptMatrix](self::OptMatrix) by implementing
+//! [CellSource](self::CellSource).
 
 
 use num;
 use image::error::ImageResult;
-use ndarray::{Array, Array2};
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use ndarray::{s, Array, Array2, ArrayBase, Data, Ix2, OwnedRepr};
 use image::{RgbaImage, Rgba};
 
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::axis::add_axis_labels;
+use crate::legend::add_legend;
 use crate::constants::colors::*;
+use crate::scale::ColorScale;
 use crate::types::Config;
 
-// -------------
-// Central trait
-// -------------
-/// The main trait of the library
-pub trait Plottable<T> {
-    fn plot(&self, config: &Config, output_image_path: &str)  -> ImageResult<()>;
-    fn max_and_min(&self) -> (T, T);
-    fn scale_matrix(&self, config: &Config) -> Self;
+/// Color of the pixel in column `x` and row `y` of an image of a matrix with
+/// `dims` (x_max, y_max) columns and rows after scaling.
+///
+/// `cell` looks up the color of the cell under the pixel, `None` for an empty
+/// cell; it is only called for pixels inside the matrix.
+/// Pixels that should be left transparent are `None`.
+pub(crate) fn pixel_color<F>(
+    x: u32,
+    y: u32,
+    dims: (u32, u32),
+    config: &Config,
+    cell: F
+) -> Option<Rgba<u8>>
+where F: FnOnce() -> Option<Rgba<u8>>
+{
+    let (x_max, y_max) = dims;
+    let scaling_factor = config.scaling_factor as u32;
+
+    // Image annotations
+    if config.annotate_image {
+        // Diagonals
+        if config.draw_diagonal && x == y {
+            return Some(RED);
+        }
+
+        // vertical and horizontal separators
+        if config.draw_boundaries
+            && (x.is_multiple_of(scaling_factor) || y.is_multiple_of(scaling_factor))
+        {
+            return Some(BLUE);
+        }
+    }
+
+    // the extra row and column past the matrix only hold annotations
+    if x >= x_max || y >= y_max {
+        return None;
+    }
+
+    cell().or(Some(WHITE))
 }
 
+/// The cells of a scaled matrix and what is drawn over them
+pub(crate) trait Cells {
+    /// Color of the cell at `[row, col]` or `None` if the cell is empty
+    fn color(&self, row: usize, col: usize) -> Option<Rgba<u8>>;
+    /// Color drawn over the pixel (x, y) of the whole scaled matrix and its
+    /// annotations, `None` where the matrix shows through
+    fn overlay(&self, _x: u32, _y: u32) -> Option<Rgba<u8>> {
+        None
+    }
+}
 
-/// A wrapper around `Array2<Option<T>>`
-#[derive(Debug)]
-pub struct OptMatrix<T>
-where T: num::Zero + num::cast::ToPrimitive + Copy  + Clone + std::cmp::PartialOrd + std::cmp::Ord
+/// Draws the rows of an image, one after the other or in parallel
+pub(crate) trait Rows<C> {
+    /// Draw the rows of an RGBA `buffer` of `width` pixels per row.
+    ///
+    /// `pixel` gets the `cells` and the (x, y) of each pixel, where the first
+    /// row of the buffer is row `first_row` of the image, and returns `None`
+    /// to leave it untouched.
+    fn draw<P>(buffer: &mut [u8], width: u32, first_row: u32, cells: &C, pixel: P)
+    where P: Fn(&C, u32, u32) -> Option<Rgba<u8>> + Sync;
+}
+
+/// Draw row `y` of the image into `row`, left to right
+fn draw_row<C, P>(row: &mut [u8], y: u32, cells: &C, pixel: &P)
+where P: Fn(&C, u32, u32) -> Option<Rgba<u8>>
 {
-    pub matrix: Array2<Option<T>>
+    for (x, channels) in row.chunks_exact_mut(4).enumerate() {
+        if let Some(color) = pixel(cells, x as u32, y) {
+            channels.copy_from_slice(&color.0);
+        }
+    }
+}
+
+/// Draws rows one after the other on the calling thread
+pub(crate) struct Sequential;
+
+impl<C> Rows<C> for Sequential {
+    fn draw<P>(buffer: &mut [u8], width: u32, first_row: u32, cells: &C, pixel: P)
+    where P: Fn(&C, u32, u32) -> Option<Rgba<u8>> + Sync
+    {
+        for (i, row) in buffer.chunks_mut(width as usize * 4).enumerate() {
+            draw_row(row, first_row + i as u32, cells, &pixel);
+        }
+    }
 }
 
-impl<T> Plottable<T> for OptMatrix<T>
-where T: num::Zero + num::cast::ToPrimitive + Clone + Copy + std::cmp::PartialOrd + std::cmp::Ord
+/// Draws rows in parallel with rayon, the result does not depend on the
+/// number of threads
+#[cfg(feature = "rayon")]
+pub(crate) struct Parallel;
+
+#[cfg(feature = "rayon")]
+impl<C: Sync> Rows<C> for Parallel {
+    fn draw<P>(buffer: &mut [u8], width: u32, first_row: u32, cells: &C, pixel: P)
+    where P: Fn(&C, u32, u32) -> Option<Rgba<u8>> + Sync
+    {
+        buffer.par_chunks_mut(width as usize * 4).enumerate().for_each(|(i, row)| {
+            draw_row(row, first_row + i as u32, cells, &pixel)
+        });
+    }
+}
+
+/// Draw a scaled matrix of `dim` (rows, cols) with its `cells` drawn by
+/// `D`, applying the [Viewport](crate::types::Viewport) and
+/// [Band](crate::types::Band) in the config.
+pub(crate) fn render_cells<D, C>(dim: (usize, usize), config: &Config, cells: &C) -> RgbaImage
+where
+    C: Cells,
+    D: Rows<C>
 {
-    fn plot(&self, config: &Config, output_image_path: &str) -> ImageResult<()> {
-        let matrix = &self.matrix;
+    let scaling_factor = config.scaling_factor as usize;
+
+    let (rows, cols) = match &config.viewport {
+        Some(viewport) => viewport.scaled(dim, config.scaling_factor),
+        None => (0..dim.0, 0..dim.1),
+    };
+
+    // pixels are drawn at their position in the whole matrix
+    let (y0, x0) = (rows.start as u32, cols.start as u32);
+    let (y_max, x_max) = (rows.end as u32, cols.end as u32);
+    let height = y_max - y0 + 1;
+
+    let (img, first_col) = match config.band {
+        // one column per diagonal, the rows stay in place
+        Some(band) if band.shear => {
+            let width = (band.hi - band.lo + 1).max(0) as u32 * scaling_factor as u32 + 1;
+            let full_width = dim.1 as isize;
+
+            let mut img = RgbaImage::new(width, height);
+            D::draw(&mut img, width, 0, cells, |cells, x, y| {
+                let y = y + y0;
+                let row = y as usize / scaling_factor;
+                let x = (row as isize + band.lo) * scaling_factor as isize + x as isize;
+                if x < 0 || x > full_width {
+                    return None;
+                }
+                let x = x as u32;
+                cells.overlay(x, y).or_else(|| {
+                    pixel_color(x, y, (full_width as u32, y_max), config, || cells.color(y as usize, x as usize))
+                })
+            });
+
+            (img, band.lo)
+        },
+        band => {
+            // we add one to allow drawing the last vertical rows and cols
+            let width = x_max - x0 + 1;
+
+            let mut img = RgbaImage::new(width, height);
+            D::draw(&mut img, width, 0, cells, |cells, x, y| {
+                let (x, y) = (x + x0, y + y0);
+                if let Some(band) = band {
+                    if !band.contains_pixel(x as usize, y as usize, scaling_factor) {
+                        return None;
+                    }
+                }
+                cells.overlay(x, y).or_else(|| {
+                    pixel_color(x, y, (x_max, y_max), config, || cells.color(y as usize, x as usize))
+                })
+            });
+
+            (img, (cols.start / scaling_factor) as isize)
+        },
+    };
+
+    match &config.viewport {
+        Some(_) => add_axis_labels(&img, rows.start / scaling_factor, first_col, scaling_factor as u32),
+        None => img,
+    }
+}
 
-        if matrix.ndim() != 2 {
-		    panic!("[ndarray-to-img::generate_image] Expected a 2D matrix")
-	    }
-	    if config.verbosity > 2 {
-		    eprintln!("[ndarray-to-img::generate_image]");
-	    }
+/// The cells of an **unscaled** matrix, scaled on the fly, with the color of
+/// each cell given by `color(row, col)`
+struct ColoredCells<F> {
+    color: F,
+    scaling_factor: usize,
+}
 
-	    if config.verbosity > 0 {
-		    eprintln!("Generating image {}", output_image_path);
-		    if config.verbosity > 1 {
-			    eprintln!("scaling factor: {}", config.scaling_factor);
-		    }
-	    }
+impl<F: Fn(usize, usize) -> Rgba<u8>> Cells for ColoredCells<F> {
+    fn color(&self, row: usize, col: usize) -> Option<Rgba<u8>> {
+        Some((self.color)(row / self.scaling_factor, col / self.scaling_factor))
+    }
+}
 
-	    let (min, max) = self.max_and_min();
+/// Draw an **unscaled** matrix of `dim` (rows, cols), scaling it on the fly,
+/// with the color of each cell given by `color(row, col)`
+fn render_colored<F>(dim: (usize, usize), config: &Config, color: F) -> RgbaImage
+where F: Fn(usize, usize) -> Rgba<u8>
+{
+    let scaling_factor = config.scaling_factor as usize;
+    let scaled_dim = (dim.0 * scaling_factor, dim.1 * scaling_factor);
 
-        // let (y_max, x_max) = matrix.dim();
-	    let matrix_dimensions: &[usize] = matrix.shape();
+    render_cells::<Sequential, _>(scaled_dim, config, &ColoredCells { color, scaling_factor })
+}
 
-	    let y_max = matrix_dimensions[0] as u32; // rows
-	    let x_max = matrix_dimensions[1] as u32; // cols
+/// Save an image, logging it like [plot](Plottable::plot)
+pub(crate) fn save_image(img: RgbaImage, config: &Config, output_image_path: &str) -> ImageResult<()> {
+    if config.verbosity > 0 {
+        eprintln!("Generating image {}", output_image_path);
+    }
 
-	    // we add one to allow drawing the last vertical rows and cols
-	    let mut img = RgbaImage::new(x_max+1, y_max+1);
+    img.save(output_image_path)
+}
 
-	    let scaling_factor = config.scaling_factor as usize;
+/// Scale a matrix by the `scaling_factor` in the config, each cell becomes a
+/// square of `scaling_factor` x `scaling_factor` cells.
+///
+/// Uses `floor(pos / scaling_factor)`.
+pub(crate) fn scale_array<A, S>(matrix: &ArrayBase<S, Ix2>, config: &Config) -> Array2<A>
+where
+    A: Clone,
+    S: Data<Elem = A>
+{
+    if config.verbosity > 2 {
+        eprintln!("[ndarray-to-img::scale_image]");
+    }
 
-	    // generate the image
-	    for x in 0..=x_max {
-		    for y in 0..=y_max {
-			    // Image annotations
+    if config.scaling_factor == 1 {
+        return matrix.to_owned();
+    }
 
-                // use if...else
-			    if config.annotate_image {
-				    // Diagonals
-				    if config.draw_diagonal && x == y {
-					    img.put_pixel(x as u32, y as u32, RED);
-					    continue;
-				    }
+    let scaling_factor = config.scaling_factor as usize;
+    let (rows, cols) = matrix.dim();
 
-				    // vertical and horizontal separators
-				    if  config.draw_boundaries
-                        && (x as usize % scaling_factor == 0)
-                        || (y as usize % scaling_factor == 0)
-                    {
-					    img.put_pixel(x as u32, y as u32, BLUE);
-					    continue;
-				    }
-			    }
+    // TODO: should it be  B_i,j = A_ceil(i/5),ceil(j/5) ?
+    Array::from_shape_fn((rows * scaling_factor, cols * scaling_factor), |(i, j)| {
+        matrix[[i / scaling_factor, j / scaling_factor]].clone()
+    })
+}
 
-                if !config.annotate_image && (x == x_max || y == y_max) {
-                    continue;
-                }
+/// The smallest and largest of `values`, both zero if there are none
+pub(crate) fn min_and_max<T, I>(values: I) -> (T, T)
+where
+    T: num::Zero + Copy + std::cmp::PartialOrd,
+    I: IntoIterator<Item = T>
+{
+    let mut values = values.into_iter();
+    let first = match values.next() {
+        Some(value) => value,
+        None => return (num::zero(), num::zero()),
+    };
+
+    values.fold((first, first), |(min, max), value| {
+        (if value < min { value } else { min }, if value > max { value } else { max })
+    })
+}
 
-			    // show pixel
-			    // we have to flip these to access the right cell in the matrix
-			    match matrix[[y as usize, x as usize]] {
-				    None => img.put_pixel(x as u32, y as u32, WHITE),
-				    Some(v) => {
-					    if v > num::zero() {
-						    let mut red = [255, 0, 0,  255];
-						    let value = v.to_f64().unwrap();
-						    let max_value = max.to_f64().unwrap();
-						    let m = u8::MAX as f64;
-						    let alpha_channel = ((value/max_value)*m).ceil() as u8;
-						    red[3] = alpha_channel;
-						    let red = Rgba::from(red);
-
-						    img.put_pixel(x as u32, y as u32, red);
-					    } else {
-						    let mut black = [0, 0, 0,  255];
-
-						    let value = v.to_f64().unwrap();
-						    let value = num::abs(value);
-
-						    let min_value = min.to_f64().unwrap();
-						    let min_value = num::abs(min_value);
-
-						    let m = u8::MAX as f64;
-						    let alpha_channel = ((value/min_value)*m).ceil() as u8;
-						    black[3] = alpha_channel;
-						    let black = Rgba::from(black);
-
-						    img.put_pixel(x as u32, y as u32, black);
-					    }
-				    }
-			    }
+// -------------
+// Central trait
+// -------------
+/// The main trait of the library
+///
+/// Implemented for every [CellSource], see there to plot a matrix type.
+pub trait Plottable<T>: CellSource<T> {
+    fn plot(&self, config: &Config, output_image_path: &str)  -> ImageResult<()>
+    where T: num::Zero + num::cast::ToPrimitive + Copy + std::cmp::PartialOrd
+    {
+	    if config.verbosity > 0 {
+		    eprintln!("Generating image {}", output_image_path);
+		    if config.verbosity > 1 {
+			    eprintln!("scaling factor: {}", config.scaling_factor);
 		    }
 	    }
 
 	    // save it
-	    img.save(output_image_path)
+	    self.render(config).save(output_image_path)
     }
+    /// Like [plot](Plottable::plot) but colored with a given `scale`, such as
+    /// a [shared](ColorScale::shared) one
+    fn plot_with_scale(&self, config: &Config, scale: &ColorScale, output_image_path: &str) -> ImageResult<()> {
+	    if config.verbosity > 0 {
+		    eprintln!("Generating image {}", output_image_path);
+	    }
 
-    fn max_and_min(&self) -> (T, T) {
-        let matrix = &self.matrix;
-
-        // find a start value
-	    let mut max = num::zero();
-	    let mut min = num::zero();
-
-	    for opt_val in matrix.iter() {
-		    match opt_val {
-			    Some(x) => {
-				    max = *x;
-				    min = *x;
-				    break;
-			    },
-			    _ => {}
-		    }
-	    };
-
-	    // compare against all other values
-	    for opt_val in matrix.iter() {
-		    match opt_val {
-			    Some(val) => {
-				    if *val > max {
-					    max = *val
-				    }
-
-				    if *val < min {
-					    min = *val
-				    }
-			    },
-			    _ => {}
+	    self.render_with_scale(config, scale).save(output_image_path)
+    }
+    /// Draw the matrix into an in-memory image without saving it
+    fn render(&self, config: &Config) -> RgbaImage
+    where T: num::Zero + num::cast::ToPrimitive + Copy + std::cmp::PartialOrd
+    {
+        self.render_with_scale(config, &ColorScale::of_scaled(self, config))
+    }
+    /// Like [render](Plottable::render) but colored with a given `scale`, such
+    /// as a [shared](ColorScale::shared) one
+    fn render_with_scale(&self, config: &Config, scale: &ColorScale) -> RgbaImage;
+    /// Like [plot](Plottable::plot) but rows are drawn in parallel, see
+    /// [par_render](Plottable::par_render)
+    #[cfg(feature = "rayon")]
+    fn par_plot(&self, config: &Config, output_image_path: &str) -> ImageResult<()>
+    where
+        T: num::Zero + num::cast::ToPrimitive + Copy + std::cmp::PartialOrd,
+        Self: Sync
+    {
+	    if config.verbosity > 0 {
+		    eprintln!("Generating image {}", output_image_path);
+		    if config.verbosity > 1 {
+			    eprintln!("scaling factor: {}", config.scaling_factor);
 		    }
 	    }
 
-	    (min, max)
+	    self.par_render(config).save(output_image_path)
     }
-
-    fn scale_matrix(&self, config: &Config) -> OptMatrix<T> {
-        let matrix = &self.matrix;
-
+    /// Like [render](Plottable::render) but rows are drawn in parallel with
+    /// rayon, the image does not depend on the number of threads
+    #[cfg(feature = "rayon")]
+    fn par_render(&self, config: &Config) -> RgbaImage
+    where
+        T: num::Zero + num::cast::ToPrimitive + Copy + std::cmp::PartialOrd,
+        Self: Sync
+    {
+        self.par_render_with_scale(config, &ColorScale::of_scaled(self, config))
+    }
+    /// Like [render_with_scale](Plottable::render_with_scale) but rows are
+    /// drawn in parallel, see [par_render](Plottable::par_render)
+    #[cfg(feature = "rayon")]
+    fn par_render_with_scale(&self, config: &Config, scale: &ColorScale) -> RgbaImage
+    where
+        T: num::cast::ToPrimitive,
+        Self: Sync
+    {
+        render_source::<Parallel, T, Self>(self, config, scale)
+    }
+    /// The smallest and largest values of the matrix, both zero if it is empty
+    fn max_and_min(&self) -> (T, T)
+    where T: num::Zero + Copy + std::cmp::PartialOrd
+    {
+        let (rows, cols) = self.dim();
+        self.max_and_min_in(0..rows, 0..cols)
+    }
+    /// The matrix scaled by the `scaling_factor` in [Config](crate::Config)
+    /// without copying its cells, each cell is looked up in the matrix when
+    /// it is drawn
+    fn scaled(&self, config: &Config) -> Scaled<&Self> {
 	    if config.verbosity > 2 {
 		    eprintln!("[ndarray-to-img::scale_image]");
 	    }
 
-	    if config.scaling_factor == 1 {
-		    return OptMatrix{ matrix: matrix.clone() };
-	    }
-
-	    let scaling_factor = config.scaling_factor as usize;
+        Scaled { source: self, scaling_factor: config.scaling_factor as usize }
+    }
+}
 
-	    let matrix_dimensions: &[usize] = matrix.shape();
 
-	    let i_max: usize = matrix_dimensions[0]; // rows
-	    let j_max: usize = matrix_dimensions[1]; // cols
+/// A wrapper around `Array2<Option<T>>`
+///
+/// Any 2D array works, including views such as slices and transposes of a
+/// larger array, without copying it.
+#[derive(Debug)]
+pub struct OptMatrix<T, S = OwnedRepr<Option<T>>>
+where
+    T: num::Zero + num::cast::ToPrimitive + Copy  + Clone + std::cmp::PartialOrd,
+    S: Data<Elem = Option<T>>
+{
+    pub matrix: ArrayBase<S, Ix2>
+}
 
-	    let scaled_i_max = i_max * scaling_factor; // scaled rows
-	    let scaled_j_max = j_max * scaling_factor; // scaled cols
+impl<T, S> OptMatrix<T, S>
+where
+    T: num::Zero + num::cast::ToPrimitive + Clone + Copy + std::cmp::PartialOrd,
+    S: Data<Elem = Option<T>>
+{
+    /// Draw the **unscaled** matrix with the color of each cell, empty or not,
+    /// given by `color(row, col, value)`.
+    ///
+    /// The matrix is scaled by the `scaling_factor` in [Config](crate::Config),
+    /// annotated and windowed like in [render](Plottable::render).
+    pub fn render_with<F>(&self, config: &Config, color: F) -> RgbaImage
+    where F: Fn(usize, usize, Option<&T>) -> Rgba<u8>
+    {
+        render_colored(self.matrix.dim(), config, |row, col| color(row, col, self.matrix[[row, col]].as_ref()))
+    }
 
-	    let mut scaled_matrix: Array2<Option<T>> = Array::from_elem((scaled_i_max, scaled_j_max), None);
+    /// The matrix scaled by the `scaling_factor` in [Config](crate::Config),
+    /// each cell becomes a square of `scaling_factor` x `scaling_factor`
+    /// cells.
+    ///
+    /// [scaled](Plottable::scaled) does the same without copying the cells.
+    pub fn scale_matrix(&self, config: &Config) -> OptMatrix<T> {
+        OptMatrix { matrix: scale_array(&self.matrix, config) }
+    }
 
-	    let scaling_factor = config.scaling_factor as f64;
+    /// Save the image of [render_with](OptMatrix::render_with)
+    pub fn plot_with<F>(&self, config: &Config, color: F, output_image_path: &str) -> ImageResult<()>
+    where F: Fn(usize, usize, Option<&T>) -> Rgba<u8>
+    {
+        save_image(self.render_with(config, color), config, output_image_path)
+    }
+}
 
-	    for i in 0..scaled_i_max {
-		    for j in 0..scaled_j_max {
-			    // TODO: should it be  B_i,j = A_ceil(i/5),ceil(j/5) ?
-			    let old_i = (i as f64/scaling_factor).floor() as usize;
-			    let old_j = (j as f64/scaling_factor).floor() as usize;
+impl<T, S> CellSource<T> for OptMatrix<T, S>
+where
+    T: num::Zero + num::cast::ToPrimitive + Clone + Copy + std::cmp::PartialOrd,
+    S: Data<Elem = Option<T>>
+{
+    fn dim(&self) -> (usize, usize) {
+        self.matrix.dim()
+    }
 
-			    scaled_matrix[[i, j]] = matrix[[old_i, old_j]].clone();
-		    }
-	    }
+    fn value_at(&self, row: usize, col: usize) -> Option<T> {
+        self.matrix[[row, col]]
+    }
 
-        OptMatrix{ matrix: scaled_matrix }
+    fn max_and_min_in(&self, rows: Range<usize>, cols: Range<usize>) -> (T, T) {
+        let matrix = self.matrix.slice(s![rows, cols]);
+        min_and_max(matrix.iter().flatten().copied())
     }
 }
 
 
 /// A wrapper around `Array2<T>`
+///
+/// Any 2D array works, including views such as slices and transposes of a
+/// larger array, without copying it.
 #[derive(Debug)]
-pub struct Matrix<T>
-where T: num::Zero + num::cast::ToPrimitive + Copy  + Clone + std::cmp::PartialOrd + std::cmp::Ord
+pub struct Matrix<T, S = OwnedRepr<T>>
+where
+    T: num::Zero + num::cast::ToPrimitive + Copy  + Clone + std::cmp::PartialOrd,
+    S: Data<Elem = T>
 {
-    pub matrix: Array2<T>
+    pub matrix: ArrayBase<S, Ix2>
 }
 
-impl<T> Plottable<T> for Matrix<T>
-where T: num::Zero + num::cast::ToPrimitive + Clone + Copy + std::cmp::PartialOrd + std::cmp::Ord
+impl<T, S> Matrix<T, S>
+where
+    T: num::Zero + num::cast::ToPrimitive + Clone + Copy + std::cmp::PartialOrd,
+    S: Data<Elem = T>
 {
-    fn plot(&self, config: &Config, output_image_path: &str) -> ImageResult<()> {
-        let matrix = &self.matrix;
-
-        if matrix.ndim() != 2 {
-		    panic!("[ndarray-to-img::generate_image] Expected a 2D matrix")
-	    }
-	    if config.verbosity > 2 {
-		    eprintln!("[ndarray-to-img::generate_image]");
-	    }
-
-	    if config.verbosity > 0 {
-		    eprintln!("Generating image {}", output_image_path);
-		    if config.verbosity > 1 {
-			    eprintln!("scaling factor: {}", config.scaling_factor);
-		    }
-	    }
-
-	    let (min, max) = self.max_and_min();
-
-        // let (y_max, x_max) = matrix.dim();
-	    let matrix_dimensions: &[usize] = matrix.shape();
+    /// Draw the **unscaled** matrix with the color of each cell, zero or not,
+    /// given by `color(row, col, value)`.
+    ///
+    /// The matrix is scaled by the `scaling_factor` in [Config](crate::Config),
+    /// annotated and windowed like in [render](Plottable::render).
+    pub fn render_with<F>(&self, config: &Config, color: F) -> RgbaImage
+    where F: Fn(usize, usize, &T) -> Rgba<u8>
+    {
+        render_colored(self.matrix.dim(), config, |row, col| color(row, col, &self.matrix[[row, col]]))
+    }
 
-	    let y_max = matrix_dimensions[0] as u32; // rows
-	    let x_max = matrix_dimensions[1] as u32; // cols
+    /// The matrix scaled by the `scaling_factor` in [Config](crate::Config),
+    /// each cell becomes a square of `scaling_factor` x `scaling_factor`
+    /// cells.
+    ///
+    /// [scaled](Plottable::scaled) does the same without copying the cells.
+    pub fn scale_matrix(&self, config: &Config) -> Matrix<T> {
+        Matrix { matrix: scale_array(&self.matrix, config) }
+    }
 
-	    // we add one to allow drawing the last vertical rows and cols
-	    let mut img = RgbaImage::new(x_max+1, y_max+1);
+    /// Save the image of [render_with](Matrix::render_with)
+    pub fn plot_with<F>(&self, config: &Config, color: F, output_image_path: &str) -> ImageResult<()>
+    where F: Fn(usize, usize, &T) -> Rgba<u8>
+    {
+        save_image(self.render_with(config, color), config, output_image_path)
+    }
+}
 
-	    let scaling_factor = config.scaling_factor as usize;
+impl<T, S> CellSource<T> for Matrix<T, S>
+where
+    T: num::Zero + num::cast::ToPrimitive + Clone + Copy + std::cmp::PartialOrd,
+    S: Data<Elem = T>
+{
+    fn dim(&self) -> (usize, usize) {
+        self.matrix.dim()
+    }
 
-	    // generate the image
-	    for x in 0..=x_max {
-		    for y in 0..=y_max {
-			    // Image annotations
+    fn value_at(&self, row: usize, col: usize) -> Option<T> {
+        let value = self.matrix[[row, col]];
+        if value == num::zero() { None } else { Some(value) }
+    }
 
-                // use if...else
-			    if config.annotate_image {
-				    // Diagonals
-				    if config.draw_diagonal && x == y {
-					    img.put_pixel(x as u32, y as u32, RED);
-					    continue;
-				    }
+    fn max_and_min_in(&self, rows: Range<usize>, cols: Range<usize>) -> (T, T) {
+        let matrix = self.matrix.slice(s![rows, cols]);
+        // zeros are empty but always in range
+        min_and_max(std::iter::once(num::zero()).chain(matrix.iter().copied()))
+    }
+}
 
-				    // vertical and horizontal separators
-				    if  config.draw_boundaries
-                        && (x as usize % scaling_factor == 0)
-                        || (y as usize % scaling_factor == 0)
-                    {
-					    img.put_pixel(x as u32, y as u32, BLUE);
-					    continue;
-				    }
-			    }
 
-                if !config.annotate_image && (x == x_max || y == y_max) {
-                    continue;
-                }
+// ------------
+// Cell sources
+// ------------
+/// Any matrix whose cells can be looked up one at a time, such as an
+/// [OptMatrix], a sparse matrix, one computed on the fly or one backed by a
+/// database.
+///
+/// Implementing [dim](CellSource::dim) and [value_at](CellSource::value_at)
+/// makes a type [Plottable], cells are only looked up while rendering.
+/// [par_render](Plottable::par_render) also needs the type to be `Sync`.
+pub trait CellSource<T> {
+    /// (rows, cols) of the matrix
+    fn dim(&self) -> (usize, usize);
+    /// The value of the cell at `[row, col]` or `None` if the cell is empty
+    fn value_at(&self, row: usize, col: usize) -> Option<T>;
+    /// The smallest and largest values of a window of the matrix, both zero
+    /// if it is empty.
+    ///
+    /// Looks up every cell of the window, sparse matrices can do better.
+    fn max_and_min_in(&self, rows: Range<usize>, cols: Range<usize>) -> (T, T)
+    where T: num::Zero + Copy + std::cmp::PartialOrd
+    {
+        min_and_max(rows.flat_map(|row| cols.clone().filter_map(move |col| self.value_at(row, col))))
+    }
+    /// The values of the non-empty cells of a window of the matrix, in no
+    /// particular order
+    fn values_in(&self, rows: Range<usize>, cols: Range<usize>) -> Vec<T> {
+        rows.flat_map(|row| cols.clone().filter_map(move |col| self.value_at(row, col))).collect()
+    }
+    /// Rows and columns a line is drawn before, such as the first bin of
+    /// each chromosome in a [ContactMap](crate::contact::ContactMap)
+    fn boundaries(&self) -> Vec<usize> {
+        Vec::new()
+    }
+}
 
-			    // show pixel
-			    // we have to flip these to access the right cell in the matrix
-                let value_at_cell = matrix[[y as usize, x as usize]];
-                match value_at_cell.cmp(&num::zero()) {
-                    std::cmp::Ordering::Equal => { img.put_pixel(x as u32, y as u32, WHITE); }
-                    std::cmp::Ordering::Less => {
-                        let mut black = [0, 0, 0,  255];
-
-						let value = value_at_cell.to_f64().unwrap();
-						let value = num::abs(value);
-
-						let min_value = min.to_f64().unwrap();
-						let min_value = num::abs(min_value);
-
-						let m = u8::MAX as f64;
-						let alpha_channel = ((value/min_value)*m).ceil() as u8;
-						black[3] = alpha_channel;
-						let black = Rgba::from(black);
-
-						img.put_pixel(x as u32, y as u32, black);
-                    },
-                    std::cmp::Ordering::Greater => {
-                        let mut red = [255, 0, 0,  255];
-						let value = value_at_cell.to_f64().unwrap();
-						let max_value = max.to_f64().unwrap();
-						let m = u8::MAX as f64;
-						let alpha_channel = ((value/max_value)*m).ceil() as u8;
-						red[3] = alpha_channel;
-						let red = Rgba::from(red);
-
-						img.put_pixel(x as u32, y as u32, red);
-                    },
-                }
-		    }
-	    }
+impl<T, C: CellSource<T> + ?Sized> CellSource<T> for &C {
+    fn dim(&self) -> (usize, usize) {
+        (**self).dim()
+    }
 
-	    // save it
-	    img.save(output_image_path)
+    fn value_at(&self, row: usize, col: usize) -> Option<T> {
+        (**self).value_at(row, col)
     }
 
-    fn max_and_min(&self) -> (T, T) {
-        let matrix = &self.matrix;
+    fn max_and_min_in(&self, rows: Range<usize>, cols: Range<usize>) -> (T, T)
+    where T: num::Zero + Copy + std::cmp::PartialOrd
+    {
+        (**self).max_and_min_in(rows, cols)
+    }
 
-        // find a start value
-	    let mut max = num::zero();
-	    let mut min = num::zero();
+    fn values_in(&self, rows: Range<usize>, cols: Range<usize>) -> Vec<T> {
+        (**self).values_in(rows, cols)
+    }
 
+    fn boundaries(&self) -> Vec<usize> {
+        (**self).boundaries()
+    }
+}
 
-	    // compare against all other values
-	    for val in matrix.iter() {
-            if *val > max {
-				max = *val
-			}
+/// A [CellSource] scaled by a scaling factor without copying its cells,
+/// what [scaled](Plottable::scaled) returns
+#[derive(Debug, Clone)]
+pub struct Scaled<C> {
+    source: C,
+    scaling_factor: usize,
+}
 
-			if *val < min {
-				min = *val
-			}
-	    }
+impl<C> Scaled<C> {
+    /// The rows or cols of the source under a range of scaled ones
+    fn unscaled(&self, range: Range<usize>) -> Range<usize> {
+        if range.is_empty() {
+            0..0
+        } else {
+            range.start / self.scaling_factor..(range.end - 1) / self.scaling_factor + 1
+        }
+    }
+}
 
-	    (min, max)
+impl<T, C: CellSource<T>> CellSource<T> for Scaled<C> {
+    fn dim(&self) -> (usize, usize) {
+        let (rows, cols) = self.source.dim();
+        (rows * self.scaling_factor, cols * self.scaling_factor)
     }
 
-    fn scale_matrix(&self, config: &Config) -> Matrix<T> {
-        let matrix = &self.matrix;
+    fn value_at(&self, row: usize, col: usize) -> Option<T> {
+        self.source.value_at(row / self.scaling_factor, col / self.scaling_factor)
+    }
 
-	    if config.verbosity > 2 {
-		    eprintln!("[ndarray-to-img::scale_image]");
-	    }
+    /// The same as in the cells of the source under the window
+    fn max_and_min_in(&self, rows: Range<usize>, cols: Range<usize>) -> (T, T)
+    where T: num::Zero + Copy + std::cmp::PartialOrd
+    {
+        self.source.max_and_min_in(self.unscaled(rows), self.unscaled(cols))
+    }
 
-	    if config.scaling_factor == 1 {
-		    return Matrix{ matrix: matrix.clone() };
-	    }
+    /// The values of the cells of the source under the window, once each
+    fn values_in(&self, rows: Range<usize>, cols: Range<usize>) -> Vec<T> {
+        self.source.values_in(self.unscaled(rows), self.unscaled(cols))
+    }
 
-	    let scaling_factor = config.scaling_factor as usize;
+    fn boundaries(&self) -> Vec<usize> {
+        self.source.boundaries().into_iter().map(|line| line * self.scaling_factor).collect()
+    }
+}
 
-	    let matrix_dimensions: &[usize] = matrix.shape();
+/// The cells of a [CellSource] colored by a [ColorScale], with lines drawn
+/// at its [boundaries](CellSource::boundaries)
+pub(crate) struct SourceCells<'a, T, C: ?Sized> {
+    source: &'a C,
+    scale: &'a ColorScale,
+    lines: Vec<u32>,
+    value: PhantomData<fn() -> T>,
+}
 
-	    let i_max: usize = matrix_dimensions[0]; // rows
-	    let j_max: usize = matrix_dimensions[1]; // cols
+impl<'a, T, C: CellSource<T> + ?Sized> SourceCells<'a, T, C> {
+    pub(crate) fn new(source: &'a C, scale: &'a ColorScale) -> Self {
+        let lines = source.boundaries().into_iter().map(|line| line as u32).collect();
+        SourceCells { source, scale, lines, value: PhantomData }
+    }
+}
 
-	    let scaled_i_max = i_max * scaling_factor; // scaled rows
-	    let scaled_j_max = j_max * scaling_factor; // scaled cols
+impl<T, C> Cells for SourceCells<'_, T, C>
+where
+    T: num::cast::ToPrimitive,
+    C: CellSource<T> + ?Sized
+{
+    fn color(&self, row: usize, col: usize) -> Option<Rgba<u8>> {
+        self.source.value_at(row, col).and_then(|v| v.to_f64()).map(|v| self.scale.color(v))
+    }
 
-	    let mut scaled_matrix: Array2<T> = Array::from_elem((scaled_i_max, scaled_j_max), num::zero());
+    fn overlay(&self, x: u32, y: u32) -> Option<Rgba<u8>> {
+        if self.lines.contains(&x) || self.lines.contains(&y) { Some(GREEN) } else { None }
+    }
+}
 
-	    let scaling_factor = config.scaling_factor as f64;
+/// Draw a scaled `source` colored by `scale`, with rows drawn by `D`, and a
+/// legend of the [Categories](crate::types::Categories) if they have one
+fn render_source<'a, D, T, C>(source: &'a C, config: &Config, scale: &'a ColorScale) -> RgbaImage
+where
+    T: num::cast::ToPrimitive,
+    C: CellSource<T> + ?Sized,
+    D: Rows<SourceCells<'a, T, C>>
+{
+    if config.verbosity > 2 {
+        eprintln!("[ndarray-to-img::render]");
+    }
 
-	    for i in 0..scaled_i_max {
-		    for j in 0..scaled_j_max {
-			    // TODO: should it be  B_i,j = A_ceil(i/5),ceil(j/5) ?
-			    let old_i = (i as f64/scaling_factor).floor() as usize;
-			    let old_j = (j as f64/scaling_factor).floor() as usize;
+    let img = render_cells::<D, _>(source.dim(), config, &SourceCells::new(source, scale));
 
-			    scaled_matrix[[i, j]] = matrix[[old_i, old_j]].clone();
-		    }
-	    }
+    match scale.legend() {
+        Some(entries) => add_legend(&img, &entries),
+        None => img,
+    }
+}
 
-        Matrix{ matrix: scaled_matrix }
+impl<T, C> Plottable<T> for C
+where
+    T: num::cast::ToPrimitive,
+    C: CellSource<T> + ?Sized
+{
+    fn render_with_scale(&self, config: &Config, scale: &ColorScale) -> RgbaImage {
+        render_source::<Sequential, T, C>(self, config, scale)
     }
 }
 
@@ -393,9 +684,37 @@ mod tests {
 	use super::*;
     use crate::tests_prelude;
 
+    #[test]
+    fn test_generate_image_without_boundaries() {
+		let mut config = tests_prelude::config();
+		config.draw_boundaries = false;
+
+		let mut matrix: Array2<Option<i32>> = Array::from_elem((3, 3), None);
+		matrix[[0,2]] = Some(4);
+
+        let matrix = OptMatrix { matrix };
+		let scaled_matrix = matrix.scale_matrix(&config);
+		let image_name = "test_no_boundaries_image.png";
+        assert_eq!(scaled_matrix.plot(&config, image_name).unwrap(), ());
+
+        // only the diagonal is drawn, the separators are not
+        let img = image::open(image_name).unwrap().to_rgba8();
+        assert_eq!(*img.get_pixel(15, 15), RED);
+        assert_eq!(*img.get_pixel(5, 10), WHITE);
+        assert_eq!(*img.get_pixel(10, 5), WHITE);
+        assert_eq!(*img.get_pixel(20, 5), Rgba([255, 0, 0, 255]));
+        // the extra row and column past the matrix stay transparent
+        assert_eq!(*img.get_pixel(30, 5), Rgba([0, 0, 0, 0]));
+        assert_eq!(*img.get_pixel(5, 30), Rgba([0, 0, 0, 0]));
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+
     #[test]
     fn test_generate_image_opt() {
-		let mut config = tests_prelude::CONFIG.clone();
+		let mut config = tests_prelude::config();
 		config.scaling_factor = 50;
 
 		let mut matrix: Array2<Option<i32>> = Array::from_elem((10, 10), None);
@@ -424,7 +743,7 @@ mod tests {
 
     #[test]
     fn test_generate_image() {
-		let mut config = tests_prelude::CONFIG.clone();
+		let mut config = tests_prelude::config();
 		config.scaling_factor = 50;
 
 		let mut matrix: Array2<i32> = Array::from_elem((10, 10), 0);
@@ -450,4 +769,204 @@ mod tests {
 			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
 		}
     }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_render_thread_count() {
+		let mut matrix: Array2<i32> = Array::from_elem((10, 10), 0);
+		matrix[[2,5]] = 7;
+		matrix[[5,4]] = -15;
+
+        let matrix = Matrix { matrix };
+        let matrix = matrix.scale_matrix(&tests_prelude::config());
+
+        let render = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| matrix.par_render(&tests_prelude::config()))
+        };
+
+        assert_eq!(render(1), render(4));
+        assert_eq!(render(4), matrix.render(&tests_prelude::config()));
+    }
+
+    #[test]
+    fn test_generate_image_view() {
+		let mut matrix: Array2<Option<i32>> = Array::from_elem((20, 30), None);
+
+		matrix[[1,2]] = Some(1);
+		matrix[[12,5]] = Some(7);
+		matrix[[14,25]] = Some(-15);
+
+        // a transposed, non-contiguous window of the matrix
+        let view = matrix.slice(ndarray::s![10..20;2, 5..30]).reversed_axes();
+        let copy = view.to_owned();
+
+        let view = OptMatrix { matrix: view };
+        let copy = OptMatrix { matrix: copy };
+
+        assert_eq!(view.dim(), (25, 5));
+        assert_eq!(view.max_and_min(), (-15, 7));
+        assert_eq!(view.render(&tests_prelude::config()), copy.render(&tests_prelude::config()));
+
+        let config = &tests_prelude::config();
+        assert_eq!(view.scale_matrix(config).render(config), copy.scale_matrix(config).render(config));
+    }
+
+    #[test]
+    fn test_generate_image_viewport() {
+		let mut config = tests_prelude::config();
+		config.viewport = Some(crate::types::Viewport { rows: 5..10, cols: 10..20, global_scale: false });
+
+		let mut matrix: Array2<Option<i32>> = Array::from_elem((20, 30), None);
+		matrix[[6,12]] = Some(7);
+		matrix[[15,25]] = Some(100);
+
+        let matrix = OptMatrix { matrix };
+        let scaled_matrix = matrix.scale_matrix(&config);
+        let img = scaled_matrix.render(&config);
+
+        // the window plus margins for the labels
+        assert_eq!(img.dimensions(), (11 + 101, 13 + 51));
+
+        // 7 is the largest value in the window
+        assert_eq!(*img.get_pixel(11 + 2*10 + 5, 13 + 10 + 5), Rgba([255, 0, 0, 255]));
+
+		config.viewport = Some(crate::types::Viewport { rows: 5..10, cols: 10..20, global_scale: true });
+        let img = scaled_matrix.render(&config);
+        assert_eq!(*img.get_pixel(11 + 2*10 + 5, 13 + 10 + 5), Rgba([255, 0, 0, 18]));
+
+		let image_name = "test_viewport_image.png";
+        assert_eq!(scaled_matrix.plot(&config, image_name).unwrap(), ());
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+
+    #[test]
+    fn test_generate_image_band() {
+		let mut config = tests_prelude::config();
+		config.band = Some(crate::types::Band::symmetric(1, false));
+
+		let mut matrix: Array2<i32> = Array::from_elem((10, 10), 0);
+		matrix[[5,6]] = 7;
+		matrix[[0,5]] = 9;
+
+        let matrix = Matrix { matrix };
+        let scaled_matrix = matrix.scale_matrix(&config);
+
+        let img = scaled_matrix.render(&config);
+        assert_eq!(img.dimensions(), (101, 101));
+        assert_eq!(*img.get_pixel(6*10 + 5, 5*10 + 5), Rgba([255, 0, 0, 199]));
+        // outside the band
+        assert_eq!(*img.get_pixel(5*10 + 5, 5), Rgba([0, 0, 0, 0]));
+
+		config.band = Some(crate::types::Band::symmetric(1, true));
+        let img = scaled_matrix.render(&config);
+        assert_eq!(img.dimensions(), (31, 101));
+        // diagonal +1 is the third column
+        assert_eq!(*img.get_pixel(2*10 + 5, 5*10 + 5), Rgba([255, 0, 0, 199]));
+
+		let image_name = "test_band_image.png";
+        assert_eq!(scaled_matrix.plot(&config, image_name).unwrap(), ());
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+
+    #[test]
+    fn test_generate_image_with_closure() {
+		let config = tests_prelude::config();
+
+		let mut matrix: Array2<Option<i32>> = Array::from_elem((10, 10), None);
+		matrix[[2,5]] = Some(7);
+        let matrix = OptMatrix { matrix };
+
+        // checkerboard with the non-empty cells on top
+        let img = matrix.render_with(&config, |row, col, value| match value {
+            Some(_) => BLUE,
+            None if (row + col) % 2 == 0 => BLACK,
+            None => WHITE,
+        });
+        assert_eq!(img.dimensions(), (101, 101));
+        assert_eq!(*img.get_pixel(55, 25), BLUE);
+        assert_eq!(*img.get_pixel(5, 15), WHITE);
+        assert_eq!(*img.get_pixel(5, 25), BLACK);
+
+		let image_name = "test_closure_image.png";
+        let matrix = Matrix { matrix: matrix.matrix.mapv(|v| v.unwrap_or(0)) };
+        let color = |row: usize, _col: usize, value: &i32| Rgba([0, 0, 0, (*value as u8) * 30 + row as u8]);
+        assert_eq!(matrix.plot_with(&config, color, image_name).unwrap(), ());
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+
+    /// The diagonal and the one above it, computed on the fly, and neither
+    /// `Clone` nor `Copy`
+    struct Bidiagonal(usize);
+
+    impl CellSource<i32> for Bidiagonal {
+        fn dim(&self) -> (usize, usize) {
+            (self.0, self.0)
+        }
+
+        fn value_at(&self, row: usize, col: usize) -> Option<i32> {
+            match col as isize - row as isize {
+                0 => Some(row as i32 - 3),
+                1 => Some(10),
+                _ => None,
+            }
+        }
+    }
+
+    /// Counts its lookups, which makes it neither `Sync` nor `Send`
+    struct Counted(std::cell::Cell<usize>);
+
+    impl CellSource<i32> for Counted {
+        fn dim(&self) -> (usize, usize) {
+            (3, 3)
+        }
+
+        fn value_at(&self, row: usize, _col: usize) -> Option<i32> {
+            self.0.set(self.0.get() + 1);
+            Some(row as i32)
+        }
+    }
+
+    #[test]
+    fn test_render_without_sync() {
+		let config = Config { scaling_factor: 1, annotate_image: false, ..tests_prelude::config() };
+		let source = Counted(std::cell::Cell::new(0));
+
+		// the range, then one lookup per pixel
+		assert_eq!(source.render(&config).dimensions(), (4, 4));
+		assert_eq!(source.0.get(), 18);
+    }
+
+    #[test]
+    fn test_generate_image_cell_source() {
+		let config = tests_prelude::config();
+		let source = Bidiagonal(10);
+
+		let matrix = OptMatrix { matrix: Array::from_shape_fn((10, 10), |(row, col)| source.value_at(row, col)) };
+		assert_eq!(source.max_and_min(), matrix.max_and_min());
+
+		let scaled_source = source.scaled(&config);
+		assert_eq!(scaled_source.dim(), (100, 100));
+		assert_eq!(scaled_source.value_at(25, 37), Some(10));
+		assert_eq!(scaled_source.render(&config), matrix.scale_matrix(&config).render(&config));
+
+		let image_name = "test_cell_source.png";
+        assert_eq!(scaled_source.plot(&config, image_name).unwrap(), ());
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
 }
@@ -0,0 +1,257 @@
+//! Python bindings
+//!
+//! The `ndarray_to_img` Python module, built with the `python` feature, for
+//! example with `maturin develop --features python`.
+//!
+//! `plot(array, **config)` draws a 2D NumPy array like a
+//! [Matrix](crate::plot::Matrix), where zeros are empty, or a masked array
+//! like an [OptMatrix](crate::plot::OptMatrix), where masked cells are empty.
+//! NaNs are empty in both.
+//!
+//! The keyword arguments are the fields of [Config](crate::Config) with a
+//! plain value, `verbosity`, `with_color`, `annotate_image`, `draw_diagonal`,
+//! `draw_boundaries`, `scaling_factor` and `transform` (one of `"linear"`,
+//! `"log10"`, `"log1p"`, `"symlog"` with a `linear_threshold`, `"power"` with
+//! a `gamma` or `"rank"`), and `output`: `"png"` for the bytes of a PNG file
+//! or `"rgba"` for a `height x width x 4` array of `uint8`.
+//! By default cells are colored, not annotated and not scaled, and the output
+//! is a PNG.
+
+use image::RgbaImage;
+use ndarray::{Array2, Array3, ArrayView2};
+use numpy::{IntoPyArray, PyReadonlyArray2};
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+use crate::html::encode_png;
+use crate::plot::{Matrix, OptMatrix, Plottable};
+use crate::types::{Config, Transform};
+
+
+/// What [plot] returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Png,
+    Rgba,
+}
+
+/// The config and output given as keyword arguments
+fn parse_config(kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<(Config, Output)> {
+    let mut config = Config::default();
+    let mut output = Output::Png;
+    // the transform and its parameter may come in any order
+    let mut transform: Option<String> = None;
+    let mut linear_threshold: Option<f64> = None;
+    let mut gamma: Option<f64> = None;
+
+    if let Some(kwargs) = kwargs {
+        for (key, value) in kwargs.iter() {
+            let key: String = key.extract()?;
+            match key.as_str() {
+                "verbosity" => config.verbosity = value.extract()?,
+                "with_color" => config.with_color = value.extract()?,
+                "annotate_image" => config.annotate_image = value.extract()?,
+                "draw_diagonal" => config.draw_diagonal = value.extract()?,
+                "draw_boundaries" => config.draw_boundaries = value.extract()?,
+                "scaling_factor" => config.scaling_factor = value.extract()?,
+                "transform" => transform = Some(value.extract()?),
+                "linear_threshold" => linear_threshold = Some(value.extract()?),
+                "gamma" => gamma = Some(value.extract()?),
+                "output" => {
+                    let name: String = value.extract()?;
+                    output = match name.as_str() {
+                        "png" => Output::Png,
+                        "rgba" => Output::Rgba,
+                        _ => return Err(PyValueError::new_err(format!(
+                            "[ndarray-to-img::plot] unknown output {:?}, expected png or rgba", name
+                        ))),
+                    };
+                },
+                _ => return Err(PyTypeError::new_err(format!(
+                    "[ndarray-to-img::plot] unexpected keyword argument {:?}", key
+                ))),
+            }
+        }
+    }
+
+    let transform = transform.unwrap_or_else(|| "linear".to_string());
+    if (linear_threshold.is_some() && transform != "symlog") || (gamma.is_some() && transform != "power") {
+        return Err(PyTypeError::new_err(
+            "[ndarray-to-img::plot] linear_threshold is only for the symlog transform and gamma for the power transform"
+        ));
+    }
+    config.transform = match transform.as_str() {
+        "linear" => Transform::Linear,
+        "log10" => Transform::Log10,
+        "log1p" => Transform::Log1p,
+        "rank" => Transform::Rank,
+        "symlog" => match linear_threshold {
+            Some(linear_threshold) if linear_threshold > 0.0 => Transform::SymLog { linear_threshold },
+            _ => return Err(PyValueError::new_err(
+                "[ndarray-to-img::plot] the symlog transform needs a positive linear_threshold"
+            )),
+        },
+        "power" => match gamma {
+            Some(gamma) => Transform::Power { gamma },
+            None => return Err(PyValueError::new_err("[ndarray-to-img::plot] the power transform needs a gamma")),
+        },
+        _ => return Err(PyValueError::new_err(format!(
+            "[ndarray-to-img::plot] unknown transform {:?}, expected linear, log10, log1p, symlog, power or rank", transform
+        ))),
+    };
+
+    if config.scaling_factor == 0 {
+        return Err(PyValueError::new_err("[ndarray-to-img::plot] scaling_factor is 0"));
+    }
+
+    Ok((config, output))
+}
+
+/// The cells of a masked array, empty where it is masked or NaN
+fn masked_cells(data: ArrayView2<f64>, mask: ArrayView2<bool>) -> Array2<Option<f64>> {
+    Array2::from_shape_fn(data.dim(), |(row, col)| {
+        let value = data[[row, col]];
+        if mask[[row, col]] || value.is_nan() { None } else { Some(value) }
+    })
+}
+
+/// The values of an array, NaNs are 0 so that they are empty too
+fn values(data: ArrayView2<f64>) -> Array2<f64> {
+    data.mapv(|value| if value.is_nan() { 0.0 } else { value })
+}
+
+/// `img` as a `height x width x 4` array
+fn rgba_array(img: RgbaImage) -> Array3<u8> {
+    let (width, height) = img.dimensions();
+    Array3::from_shape_vec((height as usize, width as usize, 4), img.into_raw())
+        .expect("[ndarray-to-img::plot] an RGBA image has 4 bytes per pixel")
+}
+
+/// Plot a 2D NumPy array, or masked array, to PNG bytes or an RGBA array
+#[pyfunction]
+#[pyo3(signature = (array, **config))]
+fn plot<'py>(
+    py: Python<'py>,
+    array: &Bound<'py, PyAny>,
+    config: Option<&Bound<'py, PyDict>>
+) -> PyResult<Bound<'py, PyAny>> {
+    let (config, output) = parse_config(config)?;
+
+    let numpy = py.import("numpy")?;
+    let ma = numpy.getattr("ma")?;
+    let masked = array.is_instance(&ma.getattr("MaskedArray")?)?;
+
+    let data = numpy.call_method1("asarray", (ma.call_method1("getdata", (array,))?, "float64"))?;
+    let data: PyReadonlyArray2<f64> = data.extract().map_err(|_| {
+        PyTypeError::new_err("[ndarray-to-img::plot] expected a 2D array of numbers")
+    })?;
+    let data = data.as_array();
+
+    if config.verbosity > 0 {
+        eprintln!("Generating image of a {}x{} array", data.nrows(), data.ncols());
+    }
+
+    let img = if masked {
+        let mask: PyReadonlyArray2<bool> = ma.call_method1("getmaskarray", (array,))?.extract()?;
+        let matrix = OptMatrix { matrix: masked_cells(data, mask.as_array()) };
+        py.detach(|| matrix.scaled(&config).render(&config))
+    } else {
+        let matrix = Matrix { matrix: values(data) };
+        py.detach(|| matrix.scaled(&config).render(&config))
+    };
+
+    match output {
+        Output::Png => {
+            let png = encode_png(&img).map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+            Ok(PyBytes::new(py, &png).into_any())
+        },
+        Output::Rgba => Ok(rgba_array(img).into_pyarray(py).into_any()),
+    }
+}
+
+/// The `ndarray_to_img` Python module
+#[pymodule]
+fn ndarray_to_img(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(plot, module)?)?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use ndarray::array;
+
+    /// `parse_config` of the keyword arguments set by `set`
+    fn parse_with<F>(set: F) -> PyResult<(Config, Output)>
+    where F: for<'py> FnOnce(&Bound<'py, PyDict>) -> PyResult<()>
+    {
+        Python::initialize();
+        Python::attach(|py| {
+            let kwargs = PyDict::new(py);
+            set(&kwargs)?;
+            parse_config(Some(&kwargs))
+        })
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let (config, output) = parse_config(None).unwrap();
+        assert_eq!((config.scaling_factor, config.transform, output), (1, Transform::Linear, Output::Png));
+
+        let (config, output) = parse_with(|kwargs| {
+            kwargs.set_item("scaling_factor", 10)?;
+            kwargs.set_item("annotate_image", true)?;
+            kwargs.set_item("output", "rgba")
+        }).unwrap();
+        assert_eq!((config.scaling_factor, config.annotate_image, output), (10, true, Output::Rgba));
+
+        let (config, _) = parse_with(|kwargs| {
+            kwargs.set_item("linear_threshold", 2.0)?;
+            kwargs.set_item("transform", "symlog")
+        }).unwrap();
+        assert_eq!(config.transform, Transform::SymLog { linear_threshold: 2.0 });
+
+        let (config, _) = parse_with(|kwargs| {
+            kwargs.set_item("transform", "power")?;
+            kwargs.set_item("gamma", 0.5)
+        }).unwrap();
+        assert_eq!(config.transform, Transform::Power { gamma: 0.5 });
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        assert!(parse_with(|kwargs| kwargs.set_item("scaling_factor", 0)).is_err());
+        assert!(parse_with(|kwargs| kwargs.set_item("colour", true)).is_err());
+        assert!(parse_with(|kwargs| kwargs.set_item("output", "jpeg")).is_err());
+        assert!(parse_with(|kwargs| kwargs.set_item("transform", "sqrt")).is_err());
+        assert!(parse_with(|kwargs| kwargs.set_item("transform", "symlog")).is_err());
+        assert!(parse_with(|kwargs| {
+            kwargs.set_item("transform", "symlog")?;
+            kwargs.set_item("linear_threshold", 0.0)
+        }).is_err());
+        assert!(parse_with(|kwargs| kwargs.set_item("transform", "power")).is_err());
+        assert!(parse_with(|kwargs| kwargs.set_item("gamma", 0.5)).is_err());
+    }
+
+    #[test]
+    fn test_cells() {
+        let data = array![[0.0, 1.0], [f64::NAN, -2.0]];
+        assert_eq!(values(data.view()), array![[0.0, 1.0], [0.0, -2.0]]);
+
+        // masked arrays keep their zeros
+        let mask = array![[false, true], [false, false]];
+        assert_eq!(masked_cells(data.view(), mask.view()), array![[Some(0.0), None], [None, Some(-2.0)]]);
+    }
+
+    #[test]
+    fn test_rgba_array() {
+        let mut img = RgbaImage::new(3, 2);
+        img.put_pixel(2, 1, image::Rgba([1, 2, 3, 4]));
+
+        let rgba = rgba_array(img);
+        assert_eq!(rgba.dim(), (2, 3, 4));
+        assert_eq!(rgba.slice(ndarray::s![1, 2, ..]).to_vec(), vec![1, 2, 3, 4]);
+    }
+}
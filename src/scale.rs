@@ -0,0 +1,420 @@
+//! Color scales
+//!
+//! Values are colored relative to the min and max of the matrix, or to the
+//! [Clip](crate::Clip) limits, after the [Transform](crate::Transform) in
+//! the config is applied to all three.
+//!
+//! Each [render](crate::plot::Plottable::render) computes its own
+//! [ColorScale](self::ColorScale), so the same color can mean different values
+//! in different images. [ColorScale::shared](self::ColorScale::shared) computes
+//! one scale over several matrices to pass to
+//! [render_with_scale](crate::plot::Plottable::render_with_scale) instead.
+
+use std::ops::Range;
+
+use image::Rgba;
+use num::cast::ToPrimitive;
+
+use crate::plot::Plottable;
+
+use crate::constants::colors::*;
+use crate::types::{Categories, Clip, Config, Limits, Transform};
+
+
+/// Color of a non-empty cell.
+///
+/// Positive values are a shade of red relative to `max`,
+/// the rest are a shade of grey relative to `min`.
+fn value_color(value: f64, min: f64, max: f64) -> Rgba<u8> {
+    let m = u8::MAX as f64;
+
+    if value > 0.0 {
+        let alpha_channel = ((value/max)*m).ceil() as u8;
+        Rgba([255, 0, 0, alpha_channel])
+    } else if min == 0.0 {
+        // 0 is the smallest value, as dark as any other smallest value
+        Rgba([0, 0, 0, u8::MAX])
+    } else {
+        let alpha_channel = ((num::abs(value)/num::abs(min))*m).ceil() as u8;
+        Rgba([0, 0, 0, alpha_channel])
+    }
+}
+
+/// Value at percentile `p` of sorted `values`, interpolating between values
+fn percentile(values: &[f64], p: f64) -> f64 {
+    let position = (p / 100.0).clamp(0.0, 1.0) * (values.len() - 1) as f64;
+    let (below, above) = (position.floor() as usize, position.ceil() as usize);
+    values[below] + (values[above] - values[below]) * (position - below as f64)
+}
+
+/// The rows and cols of a matrix scaled by `scaling_factor` the colors are
+/// relative to, the viewport unless it uses a global scale
+fn window(dim: (usize, usize), config: &Config, scaling_factor: u8) -> (Range<usize>, Range<usize>) {
+    match &config.viewport {
+        Some(viewport) if !viewport.global_scale => viewport.scaled(dim, scaling_factor),
+        _ => (0..dim.0, 0..dim.1),
+    }
+}
+
+/// Non-empty values in the [window] of a matrix scaled by `scaling_factor`,
+/// as given by its [values_in](crate::plot::CellSource::values_in)
+fn values<T, P>(matrix: &P, config: &Config, scaling_factor: u8) -> Vec<f64>
+where
+    T: ToPrimitive,
+    P: Plottable<T> + ?Sized
+{
+    let (rows, cols) = window(matrix.dim(), config, scaling_factor);
+
+    matrix.values_in(rows, cols).into_iter().filter_map(|v| v.to_f64()).collect()
+}
+
+/// Maps values to colors
+#[derive(Debug, Clone)]
+pub struct ColorScale {
+    transform: Transform,
+    clip: Option<Clip>,
+    /// limits before the transform
+    lo: f64,
+    hi: f64,
+    /// limits after the transform
+    min: f64,
+    max: f64,
+    /// sorted magnitudes of the positive and negative values, for ranks
+    positive: Vec<f64>,
+    negative: Vec<f64>,
+    categories: Option<Categories>,
+    /// color and label of each category, sorted by value
+    palette: Vec<(i64, Rgba<u8>, String)>,
+}
+
+impl ColorScale {
+    /// Colors relative to `min` and `max`, or to the [Clip](crate::Clip)
+    /// limits in the config.
+    ///
+    /// [Rank](Transform::Rank), [Percentiles](Limits::Percentiles) and
+    /// [Categories] also need the values of the matrix, see
+    /// [with_values](ColorScale::with_values).
+    pub fn new(min: f64, max: f64, config: &Config) -> Self {
+        let (lo, hi) = match config.clip {
+            Some(Clip { limits: Limits::Values { min, max }, .. }) => (min, max),
+            _ => (min, max),
+        };
+
+        let scale = ColorScale {
+            transform: config.transform,
+            clip: config.clip,
+            lo: 0.0,
+            hi: 0.0,
+            min: 0.0,
+            max: 0.0,
+            positive: Vec::new(),
+            negative: Vec::new(),
+            categories: config.categories.clone(),
+            palette: Vec::new(),
+        };
+        scale.with_limits(lo, hi)
+    }
+
+    /// One scale over all the **unscaled** `matrices`, as if they were a
+    /// single matrix.
+    ///
+    /// Pass it to [render_with_scale](crate::plot::Plottable::render_with_scale)
+    /// or [plot_with_scale](crate::plot::Plottable::plot_with_scale) to color
+    /// the same value the same way in every image.
+    pub fn shared<'a, T, P, I>(matrices: I, config: &Config) -> Self
+    where
+        T: ToPrimitive + num::Zero + Copy + PartialOrd,
+        P: Plottable<T> + ?Sized + 'a,
+        I: IntoIterator<Item = &'a P>
+    {
+        let matrices: Vec<&P> = matrices.into_iter().collect();
+        Self::over(&matrices, config, 1)
+    }
+
+    /// The scale [render](crate::plot::Plottable::render) uses for a scaled
+    /// `matrix`
+    pub(crate) fn of_scaled<T, P>(matrix: &P, config: &Config) -> Self
+    where
+        T: ToPrimitive + num::Zero + Copy + PartialOrd,
+        P: Plottable<T> + ?Sized
+    {
+        Self::over(&[matrix], config, config.scaling_factor)
+    }
+
+    /// The scale over `matrices` that have been scaled by `scaling_factor`
+    fn over<T, P>(matrices: &[&P], config: &Config, scaling_factor: u8) -> Self
+    where
+        T: ToPrimitive + num::Zero + Copy + PartialOrd,
+        P: Plottable<T> + ?Sized
+    {
+        let mut limits: Option<(f64, f64)> = None;
+        for matrix in matrices {
+            let (rows, cols) = window(matrix.dim(), config, scaling_factor);
+            // an empty window has nothing to compare
+            if rows.is_empty() || cols.is_empty() {
+                continue;
+            }
+            let (min, max) = matrix.max_and_min_in(rows, cols);
+            let (min, max) = (min.to_f64().unwrap(), max.to_f64().unwrap());
+            limits = Some(match limits {
+                Some((lo, hi)) => (lo.min(min), hi.max(max)),
+                None => (min, max),
+            });
+        }
+
+        let (min, max) = limits.unwrap_or((0.0, 0.0));
+        let scale = ColorScale::new(min, max, config);
+        if !scale.needs_values() {
+            return scale;
+        }
+
+        let values = matrices.iter().flat_map(|matrix| values(*matrix, config, scaling_factor));
+        scale.with_values(values)
+    }
+
+    fn with_limits(mut self, lo: f64, hi: f64) -> Self {
+        self.lo = lo;
+        self.hi = hi;
+        (self.min, self.max) = match self.transform {
+            Transform::Rank => (-1.0, 1.0),
+            transform => (transform.apply(lo), transform.apply(hi)),
+        };
+        self
+    }
+
+    fn percentiles(&self) -> Option<(f64, f64)> {
+        match self.clip {
+            Some(Clip { limits: Limits::Percentiles { lo, hi }, .. }) => Some((lo, hi)),
+            _ => None,
+        }
+    }
+
+    /// Does the scale need all the values to color one?
+    pub fn needs_values(&self) -> bool {
+        self.transform == Transform::Rank || self.percentiles().is_some() || self.categories.is_some()
+    }
+
+    /// The scale with ranks, percentiles and categories taken among `values`, ignored
+    /// unless it [needs_values](ColorScale::needs_values)
+    pub fn with_values<I: IntoIterator<Item = f64>>(mut self, values: I) -> Self {
+        if !self.needs_values() {
+            return self;
+        }
+
+        let mut values: Vec<f64> = values.into_iter().collect();
+        values.sort_by(f64::total_cmp);
+
+        if let Some((lo, hi)) = self.percentiles() {
+            if !values.is_empty() {
+                self = self.with_limits(percentile(&values, lo), percentile(&values, hi));
+            }
+        }
+
+        if let Some(categories) = &self.categories {
+            let mut keys: Vec<i64> = values.iter().map(|v| v.round() as i64).collect();
+            keys.extend(categories.colors.iter().map(|&(key, _)| key));
+            keys.sort_unstable();
+            keys.dedup();
+
+            let mut unlisted = QUALITATIVE.iter().cycle();
+            self.palette = keys
+                .into_iter()
+                .map(|key| {
+                    let color = match categories.colors.iter().find(|&&(k, _)| k == key) {
+                        Some(&(_, color)) => color,
+                        None => *unlisted.next().unwrap(),
+                    };
+                    let label = match categories.labels.iter().find(|(k, _)| *k == key) {
+                        Some((_, label)) => label.clone(),
+                        None => key.to_string(),
+                    };
+                    (key, color, label)
+                })
+                .collect();
+        }
+
+        if self.transform == Transform::Rank {
+            self.negative = values.iter().rev().filter(|&&v| v < 0.0).map(|v| -v).collect();
+            self.positive = values.into_iter().filter(|&v| v > 0.0).collect();
+        }
+
+        self
+    }
+
+    /// Fraction of the values of the same sign with a magnitude at most `|value|`
+    fn rank(&self, value: f64) -> f64 {
+        let magnitudes = if value > 0.0 { &self.positive } else { &self.negative };
+        if value == 0.0 || magnitudes.is_empty() {
+            return 0.0;
+        }
+
+        let at_most = magnitudes.partition_point(|&m| m <= value.abs());
+        (at_most as f64 / magnitudes.len() as f64).copysign(value)
+    }
+
+    /// `(label, color)` of each category for the legend, if there is one
+    pub(crate) fn legend(&self) -> Option<Vec<(String, Rgba<u8>)>> {
+        match &self.categories {
+            Some(categories) if categories.legend => {
+                Some(self.palette.iter().map(|(_, color, label)| (label.clone(), *color)).collect())
+            },
+            _ => None,
+        }
+    }
+
+    /// Color of a non-empty cell holding `value`.
+    ///
+    /// With [Categories], values the scale has not seen are black.
+    pub fn color(&self, value: f64) -> Rgba<u8> {
+        if self.categories.is_some() {
+            let key = value.round() as i64;
+            return match self.palette.binary_search_by_key(&key, |&(k, _, _)| k) {
+                Ok(i) => self.palette[i].1,
+                Err(_) => BLACK,
+            };
+        }
+
+        let value = match self.clip {
+            Some(clip) if value < self.lo => match clip.under_color {
+                Some(color) => return color,
+                None => self.lo,
+            },
+            Some(clip) if value > self.hi => match clip.over_color {
+                Some(color) => return color,
+                None => self.hi,
+            },
+            _ => value,
+        };
+
+        let value = match self.transform {
+            Transform::Rank => self.rank(value),
+            transform => transform.apply(value),
+        };
+
+        value_color(value, self.min, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::plot::Matrix;
+    use crate::tests_prelude;
+    use ndarray::array;
+
+    fn config(transform: Transform, clip: Option<Clip>) -> Config {
+        Config { transform, clip, ..tests_prelude::config() }
+    }
+
+    #[test]
+    fn test_transforms() {
+        let alpha = |scale: &ColorScale, value: f64| scale.color(value).0[3];
+
+        let linear = ColorScale::new(-190.0, 10.0, &config(Transform::Linear, None));
+        assert_eq!(alpha(&linear, 1.0), 26);
+        assert_eq!(alpha(&linear, -19.0), 26);
+        assert_eq!(alpha(&linear, 0.0), 0);
+
+        let from_zero = ColorScale::new(0.0, 10.0, &config(Transform::Linear, None));
+        assert_eq!(from_zero.color(0.0), BLACK);
+        assert_eq!(alpha(&from_zero, 5.0), 128);
+
+        let log = ColorScale::new(-190.0, 10.0, &config(Transform::Log1p, None));
+        assert_eq!(alpha(&log, 1.0), 74);
+        assert_eq!(alpha(&log, -190.0), 255);
+
+        let symlog = ColorScale::new(-1000.0, 1000.0, &config(Transform::SymLog { linear_threshold: 10.0 }, None));
+        assert_eq!(alpha(&symlog, 5.0), 43);
+        assert_eq!(alpha(&symlog, -1000.0), 255);
+
+        let gamma = ColorScale::new(0.0, 100.0, &config(Transform::Power { gamma: 0.5 }, None));
+        assert_eq!(alpha(&gamma, 25.0), 128);
+
+        let rank = ColorScale::new(-190.0, 10.0, &config(Transform::Rank, None))
+            .with_values(vec![1.0, 7.0, 10.0, 5.0, -190.0]);
+        assert_eq!(alpha(&rank, 1.0), 64);
+        assert_eq!(alpha(&rank, 7.0), 192);
+        assert_eq!(alpha(&rank, -190.0), 255);
+    }
+
+    #[test]
+    fn test_clip() {
+        let clip = Clip {
+            limits: Limits::Values { min: -10.0, max: 10.0 },
+            under_color: Some(BLUE),
+            over_color: None,
+        };
+        let scale = ColorScale::new(-190.0, 10.0, &config(Transform::Linear, Some(clip)));
+        assert_eq!(scale.color(-190.0), BLUE);
+        assert_eq!(scale.color(-5.0).0[3], 128);
+        assert_eq!(scale.color(100.0), scale.color(10.0));
+
+        let clip = Clip { limits: Limits::Percentiles { lo: 0.0, hi: 50.0 }, under_color: None, over_color: Some(GREEN) };
+        let scale = ColorScale::new(-190.0, 10.0, &config(Transform::Linear, Some(clip)))
+            .with_values(vec![1.0, 7.0, 10.0, 5.0, -190.0]);
+        assert_eq!(scale.color(7.0), GREEN);
+        assert_eq!(scale.color(5.0).0[3], 255);
+        assert_eq!(scale.color(1.0).0[3], 51);
+    }
+
+    #[test]
+    fn test_rank_values() {
+        let config = Config { annotate_image: false, ..config(Transform::Rank, None) };
+        let matrix = Matrix { matrix: array![[1, 2], [3, 4]] };
+
+        // 2 is the second of four values however the matrix is scaled
+        assert_eq!(matrix.render(&config).get_pixel(1, 0).0[3], 128);
+        assert_eq!(matrix.scaled(&config).render(&config).get_pixel(15, 5).0[3], 128);
+        assert_eq!(matrix.scale_matrix(&config).render(&config).get_pixel(15, 5).0[3], 128);
+    }
+
+    #[test]
+    fn test_shared_scale() {
+        let config = tests_prelude::config();
+
+        let small = Matrix { matrix: array![[1, 0], [0, 2]] };
+        let large = Matrix { matrix: array![[10, 0], [0, 0]] };
+        let scale = ColorScale::shared([&small, &large], &config);
+
+        let scaled = small.scale_matrix(&config);
+        assert_eq!(scaled.render(&config).get_pixel(5, 3).0[3], 128);
+        assert_eq!(scaled.render_with_scale(&config, &scale).get_pixel(5, 3).0[3], 26);
+
+		let image_name = "test_shared_scale.png";
+        assert_eq!(scaled.plot_with_scale(&config, &scale, image_name).unwrap(), ());
+        let img = image::open(image_name).unwrap().to_rgba8();
+        assert_eq!(img, scaled.render_with_scale(&config, &scale));
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+
+    #[test]
+    fn test_categories() {
+        let categories = Categories {
+            colors: vec![(2, RED)],
+            labels: vec![(1, "match".to_string())],
+            legend: true,
+        };
+        let config = Config { categories: Some(categories), ..tests_prelude::config() };
+
+        let scale = ColorScale::new(0.0, 3.0, &config).with_values(vec![3.0, 1.0, 1.0, 0.9]);
+        assert_eq!(scale.color(1.0), QUALITATIVE[0]);
+        assert_eq!(scale.color(2.0), RED);
+        assert_eq!(scale.color(3.0), QUALITATIVE[1]);
+        assert_eq!(scale.color(4.0), BLACK);
+
+        let legend: Vec<String> = scale.legend().unwrap().into_iter().map(|(label, _)| label).collect();
+        assert_eq!(legend, vec!["match", "2", "3"]);
+
+        let matrix = Matrix { matrix: array![[1, 2], [0, 3]] };
+        let matrix = matrix.scale_matrix(&config);
+		let image_name = "test_categories.png";
+        assert_eq!(matrix.plot(&config, image_name).unwrap(), ());
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+}
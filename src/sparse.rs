@@ -0,0 +1,118 @@
+//! Sparse matrices
+//!
+//! Matrices too large to hold as a dense `Array2` stored as a list of
+//! `(row, col, value)` triplets.
+
+use std::ops::Range;
+
+use crate::plot::{min_and_max, CellSource};
+
+/// A sparse matrix of `nrows` x `ncols` holding only the non-empty cells
+///
+/// It is [Plottable](crate::plot::Plottable) through [CellSource], cells are
+/// found with a binary search in the `entries`.
+#[derive(Debug, Clone)]
+pub struct SparseMatrix<T> {
+    pub nrows: usize,
+    pub ncols: usize,
+    /// `(row, col, value)` triplets sorted by row then column, at most one per
+    /// cell
+    pub entries: Vec<(usize, usize, T)>,
+}
+
+impl<T> SparseMatrix<T> {
+    /// An empty `nrows` x `ncols` matrix
+    pub fn new(nrows: usize, ncols: usize) -> Self {
+        SparseMatrix { nrows, ncols, entries: Vec::new() }
+    }
+
+    /// Set the value at `[row, col]`, replacing the value already there.
+    ///
+    /// Cheapest when cells are pushed in order.
+    /// Panics if the cell is outside the matrix.
+    pub fn push(&mut self, row: usize, col: usize, value: T) {
+        if row >= self.nrows || col >= self.ncols {
+            panic!(
+                "[ndarray-to-img::SparseMatrix::push] cell [{}, {}] is outside a {}x{} matrix",
+                row, col, self.nrows, self.ncols
+            );
+        }
+
+        match self.entries.last() {
+            Some(&(r, c, _)) if (r, c) >= (row, col) => match self.find(row, col) {
+                Ok(i) => self.entries[i].2 = value,
+                Err(i) => self.entries.insert(i, (row, col, value)),
+            },
+            _ => self.entries.push((row, col, value)),
+        }
+    }
+
+    /// (rows, cols) of the matrix
+    pub fn dim(&self) -> (usize, usize) {
+        (self.nrows, self.ncols)
+    }
+
+    /// Index of the entry of `[row, col]` or where it would be inserted
+    fn find(&self, row: usize, col: usize) -> Result<usize, usize> {
+        self.entries.binary_search_by_key(&(row, col), |&(r, c, _)| (r, c))
+    }
+}
+
+impl<T: Copy> CellSource<T> for SparseMatrix<T> {
+    fn dim(&self) -> (usize, usize) {
+        (self.nrows, self.ncols)
+    }
+
+    fn value_at(&self, row: usize, col: usize) -> Option<T> {
+        self.find(row, col).ok().map(|i| self.entries[i].2)
+    }
+
+    /// Only looks at the entries of the rows of the window
+    fn max_and_min_in(&self, rows: Range<usize>, cols: Range<usize>) -> (T, T)
+    where T: num::Zero + Copy + std::cmp::PartialOrd
+    {
+        min_and_max(self.values_of(rows, cols))
+    }
+
+    /// Only looks at the entries of the rows of the window
+    fn values_in(&self, rows: Range<usize>, cols: Range<usize>) -> Vec<T> {
+        self.values_of(rows, cols).collect()
+    }
+}
+
+impl<T: Copy> SparseMatrix<T> {
+    /// Values of the entries in a window
+    fn values_of(&self, rows: Range<usize>, cols: Range<usize>) -> impl Iterator<Item = T> + '_ {
+        let start = self.entries.partition_point(|&(r, _, _)| r < rows.start);
+        let end = self.entries.partition_point(|&(r, _, _)| r < rows.end).max(start);
+        self.entries[start..end]
+            .iter()
+            .filter(move |(_, col, _)| cols.contains(col))
+            .map(|&(_, _, value)| value)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::plot::Plottable;
+
+    #[test]
+    fn test_sparse_matrix() {
+        let mut matrix: SparseMatrix<i32> = SparseMatrix::new(4, 5);
+        matrix.push(0, 1, 3);
+        matrix.push(2, 4, -7);
+        // out of order and replacing a value
+        matrix.push(1, 0, 9);
+        matrix.push(0, 1, 2);
+
+        assert_eq!(matrix.entries, vec![(0, 1, 2), (1, 0, 9), (2, 4, -7)]);
+        assert_eq!(matrix.value_at(1, 0), Some(9));
+        assert_eq!(matrix.value_at(1, 1), None);
+        assert_eq!(matrix.max_and_min(), (-7, 9));
+        assert_eq!(matrix.max_and_min_in(0..2, 1..5), (2, 2));
+        assert_eq!(matrix.max_and_min_in(3..4, 0..5), (0, 0));
+        assert_eq!(matrix.values_in(0..3, 0..2), vec![2, 9]);
+    }
+}
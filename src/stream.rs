@@ -0,0 +1,238 @@
+//! Streaming output
+//!
+//! [plot](crate::plot::Plottable::plot) holds the scaled matrix and the whole
+//! image in memory, which does not work for very large outputs.
+//! Here the image is rendered in horizontal strips straight from the
+//! **unscaled** matrix and each strip is handed to the PNG encoder before the
+//! next one is drawn.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use image::error::ImageResult;
+use num::cast::ToPrimitive;
+
+use crate::animate::png_error;
+use crate::constants::colors::WHITE;
+use crate::legend::legend_panel;
+use crate::plot::{pixel_color, Cells, Plottable, Rows, Sequential, SourceCells};
+use crate::scale::ColorScale;
+use crate::types::Config;
+
+
+/// Plot an **unscaled** matrix without holding the whole image in memory.
+///
+/// The matrix is scaled on the fly by the `scaling_factor` in
+/// [Config](crate::Config). At most `memory_budget` bytes of pixel data are
+/// held at a time; a strip is never less than one row of the image.
+/// The output is identical to scaling the matrix and calling
+/// [plot](crate::plot::Plottable::plot), legend of the
+/// [Categories](crate::Categories) and lines at the
+/// [boundaries](crate::plot::CellSource::boundaries) included, except that the
+/// `viewport` and `band` are ignored.
+/// A [Rank](crate::Transform::Rank) transform or
+/// [Percentiles](crate::Limits::Percentiles) keep every value in memory.
+pub fn plot_streaming<T, P>(
+    matrix: &P,
+    config: &Config,
+    memory_budget: usize,
+    output_image_path: &str
+) -> ImageResult<()>
+where
+    T: ToPrimitive + num::Zero + Copy + PartialOrd,
+    P: Plottable<T>
+{
+    let (rows, cols) = matrix.dim();
+    let scaling_factor = config.scaling_factor as u32;
+
+    let x_max = cols as u32 * scaling_factor;
+    let y_max = rows as u32 * scaling_factor;
+
+    // we add one to allow drawing the last vertical rows and cols
+    let width = x_max + 1;
+    let height = y_max + 1;
+
+    if config.verbosity > 0 {
+        eprintln!("Generating image {}", output_image_path);
+        if config.verbosity > 1 {
+            eprintln!("scaling factor: {}", config.scaling_factor);
+        }
+    }
+
+    let (min, max) = matrix.max_and_min();
+    let scale = ColorScale::new(min.to_f64().unwrap(), max.to_f64().unwrap(), config);
+    let scale = if scale.needs_values() {
+        scale.with_values(matrix.values_in(0..rows, 0..cols).into_iter().filter_map(|v| v.to_f64()))
+    } else {
+        scale
+    };
+
+    // the legend is small, it is drawn once and copied next to each strip
+    let legend = scale.legend().map(|entries| legend_panel(&entries));
+    let (total_width, total_height) = match &legend {
+        Some(panel) => (width + panel.width(), height.max(panel.height())),
+        None => (width, height),
+    };
+
+    let row_bytes = width as usize * 4;
+    let strip_rows = (memory_budget / (total_width as usize * 4)).max(1);
+
+    // cells are looked up in the unscaled matrix as they are drawn
+    let scaled = matrix.scaled(config);
+    let cells = SourceCells::new(&scaled, &scale);
+
+    if config.verbosity > 1 {
+        eprintln!("rows per strip: {}", strip_rows);
+    }
+
+    let file = BufWriter::new(File::create(output_image_path)?);
+    let mut encoder = png::Encoder::new(file, total_width, total_height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    let mut stream = writer.stream_writer().map_err(png_error)?;
+
+    let mut strip: Vec<u8> = Vec::with_capacity(strip_rows.min(height as usize) * row_bytes);
+
+    for strip_start in (0..total_height).step_by(strip_rows) {
+        let strip_end = total_height.min(strip_start + strip_rows as u32);
+        let image_end = strip_end.min(height).max(strip_start);
+
+        // start from transparent pixels
+        strip.clear();
+        strip.resize((image_end - strip_start) as usize * row_bytes, 0);
+
+        Sequential::draw(&mut strip, width, strip_start, &cells, |cells, x, y| {
+            cells.overlay(x, y).or_else(|| {
+                pixel_color(x, y, (x_max, y_max), config, || cells.color(y as usize, x as usize))
+            })
+        });
+
+        match &legend {
+            None => stream.write_all(&strip)?,
+            Some(panel) => {
+                // like add_legend, white wherever the image or the panel ends
+                for y in strip_start..strip_end {
+                    if y < image_end {
+                        let start = (y - strip_start) as usize * row_bytes;
+                        stream.write_all(&strip[start..start + row_bytes])?;
+                    } else {
+                        write_white(&mut stream, width)?;
+                    }
+                    if y < panel.height() {
+                        let panel_row = panel.width() as usize * 4;
+                        let start = y as usize * panel_row;
+                        stream.write_all(&panel.as_raw()[start..start + panel_row])?;
+                    } else {
+                        write_white(&mut stream, panel.width())?;
+                    }
+                }
+            },
+        }
+    }
+
+    stream.finish().map_err(png_error)?;
+
+    Ok(())
+}
+
+/// Write `count` white pixels
+fn write_white<W: Write>(writer: &mut W, count: u32) -> std::io::Result<()> {
+    for _ in 0..count {
+        writer.write_all(&WHITE.0)?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::constants::colors::GREEN;
+    use crate::contact::ContactMap;
+    use crate::plot::OptMatrix;
+    use crate::tests_prelude;
+    use crate::types::Categories;
+    use ndarray::{Array, Array2};
+
+    #[test]
+    fn test_plot_streaming() {
+		let config = tests_prelude::config();
+
+		let mut matrix: Array2<Option<i32>> = Array::from_elem((10, 12), None);
+
+		matrix[[1,2]] = Some(1);
+		matrix[[2,5]] = Some(7);
+		matrix[[4,5]] = Some(10);
+		matrix[[5,4]] = Some(-15);
+		matrix[[8,11]] = Some(-190);
+
+        let matrix = OptMatrix {
+            matrix,
+        };
+
+		let image_name = "test_streamed_image.png";
+        // a budget of a few rows to get many strips
+        assert_eq!(plot_streaming(&matrix, &config, 3 * 121 * 4 + 7, image_name).unwrap(), ());
+
+        let streamed = image::open(image_name).unwrap().to_rgba8();
+        let rendered = matrix.scale_matrix(&config).render(&config);
+        assert_eq!(streamed, rendered);
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+
+    #[test]
+    fn test_plot_streaming_contact_map() {
+		let config = tests_prelude::config();
+
+        let mut map = ContactMap::new(6);
+        for i in 0..6 {
+            map.add(i, 5 - i, (i + 1) as f64);
+        }
+        let map = map.with_boundaries(vec![2, 4]);
+
+		let image_name = "test_streamed_contact_map.png";
+        assert_eq!(plot_streaming(&map, &config, 5 * 61 * 4, image_name).unwrap(), ());
+
+        // the chromosome boundaries are drawn like in render
+        let streamed = image::open(image_name).unwrap().to_rgba8();
+        assert_eq!(*streamed.get_pixel(20, 5), GREEN);
+        assert_eq!(streamed, map.scaled(&config).render(&config));
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+
+    #[test]
+    fn test_plot_streaming_legend() {
+        let categories = Categories {
+            colors: vec![],
+            labels: vec![(1, "match".to_string())],
+            legend: true,
+        };
+        let config = Config { scaling_factor: 2, categories: Some(categories), ..tests_prelude::config() };
+
+        let mut matrix: Array2<Option<i32>> = Array::from_elem((3, 3), None);
+        matrix[[0,0]] = Some(1);
+        matrix[[1,1]] = Some(2);
+        matrix[[2,2]] = Some(3);
+        let matrix = OptMatrix { matrix };
+
+		let image_name = "test_streamed_legend.png";
+        // the legend is taller than the 7x7 image, strips of two rows
+        assert_eq!(plot_streaming(&matrix, &config, 2 * 60 * 4, image_name).unwrap(), ());
+
+        let streamed = image::open(image_name).unwrap().to_rgba8();
+        let rendered = matrix.scale_matrix(&config).render(&config);
+        assert!(rendered.height() > 7);
+        assert_eq!(streamed, rendered);
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+}
@@ -3,11 +3,13 @@ use crate::types;
 
 pub const CLEANUP_TESTS: bool = false;
 
-pub static CONFIG: types::Config =  types::Config {
-	verbosity: 1,
-	with_color: true,
-	annotate_image: true,
-	draw_diagonal: true,
-	draw_boundaries: true,
-	scaling_factor: 10,
-};
+pub fn config() -> types::Config {
+	types::Config {
+		verbosity: 1,
+		annotate_image: true,
+		draw_diagonal: true,
+		draw_boundaries: true,
+		scaling_factor: 10,
+		..Default::default()
+	}
+}
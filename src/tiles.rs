@@ -0,0 +1,373 @@
+//! Deep zoom tile pyramids
+//!
+//! Matrices far too big for a single image, such as a 1M x 1M sparse matrix,
+//! are written as a pyramid of square PNG tiles in the XYZ layout
+//! `<output_dir>/<zoom>/<x>/<y>.png`, where `x` is the tile column and `y`
+//! the tile row.
+//!
+//! At the deepest zoom level one pixel is one cell, each level above halves
+//! the resolution by aggregating 2x2 pixels of the level below, down to level
+//! 0 where the whole matrix fits in a single tile.
+//! Tiles without any non-empty cell are not written.
+//!
+//! An `index.html` viewer that needs no external assets is written next to the
+//! tiles, open it in a browser to pan and zoom around the matrix.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use image::RgbaImage;
+use image::error::{ImageError, ImageResult, ParameterError, ParameterErrorKind};
+use num::cast::ToPrimitive;
+
+use crate::plot::min_and_max;
+use crate::scale::ColorScale;
+use crate::sparse::SparseMatrix;
+use crate::types::Config;
+
+
+/// How the cells falling into one pixel of a zoomed out level are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    /// The value with the largest magnitude, sign included
+    Max,
+    /// Mean of the non-empty cells
+    Mean,
+    /// Sum of the cells
+    Sum,
+    /// Number of non-empty cells, shows density
+    Count,
+}
+
+/// Configuration for the tile pyramid
+#[derive(Debug, Clone)]
+pub struct TileConfig {
+    /// Width and height of a tile in pixels
+    pub tile_size: u32,
+    pub aggregation: Aggregation,
+}
+
+impl Default for TileConfig {
+    fn default() -> Self {
+        TileConfig { tile_size: 256, aggregation: Aggregation::Max }
+    }
+}
+
+/// Running aggregate of the cells in a pixel
+#[derive(Debug, Clone, Copy)]
+struct Accumulator {
+    sum: f64,
+    count: u64,
+    max: f64,
+}
+
+impl Accumulator {
+    fn new(value: f64) -> Self {
+        Accumulator { sum: value, count: 1, max: value }
+    }
+
+    fn merge(&mut self, other: &Accumulator) {
+        self.sum += other.sum;
+        self.count += other.count;
+        if num::abs(other.max) > num::abs(self.max) {
+            self.max = other.max;
+        }
+    }
+
+    fn value(&self, aggregation: Aggregation) -> f64 {
+        match aggregation {
+            Aggregation::Max => self.max,
+            Aggregation::Mean => self.sum / self.count as f64,
+            Aggregation::Sum => self.sum,
+            Aggregation::Count => self.count as f64,
+        }
+    }
+}
+
+/// Pixels of one zoom level keyed by (row, col)
+type Level = HashMap<(usize, usize), Accumulator>;
+
+/// Number of zoom levels below level 0
+fn max_zoom(nrows: usize, ncols: usize, tile_size: u32) -> u32 {
+    let size = nrows.max(ncols).max(1);
+    let mut zoom = 0;
+    while (tile_size as usize) << zoom < size {
+        zoom += 1;
+    }
+    zoom
+}
+
+/// Halve the resolution of a level
+fn coarsen(level: &Level) -> Level {
+    let mut coarser: Level = HashMap::with_capacity(level.len() / 2);
+    for (&(row, col), acc) in level {
+        coarser
+            .entry((row / 2, col / 2))
+            .and_modify(|a| a.merge(acc))
+            .or_insert(*acc);
+    }
+    coarser
+}
+
+fn write_level(
+    level: &Level,
+    zoom: u32,
+    tile_config: &TileConfig,
+    config: &Config,
+    output_dir: &Path
+) -> ImageResult<usize> {
+    let aggregation = tile_config.aggregation;
+    let tile_size = tile_config.tile_size as usize;
+
+    // each level has its own scale because sums and counts grow as we zoom out
+    let (min, max) = min_and_max(level.values().map(|acc| acc.value(aggregation)));
+    let scale = ColorScale::new(min, max, config)
+        .with_values(level.values().map(|acc| acc.value(aggregation)));
+
+    let mut tiles: HashMap<(usize, usize), RgbaImage> = HashMap::new();
+    for (&(row, col), acc) in level {
+        let tile = tiles
+            .entry((col / tile_size, row / tile_size))
+            .or_insert_with(|| RgbaImage::new(tile_config.tile_size, tile_config.tile_size));
+
+        let color = scale.color(acc.value(aggregation));
+        tile.put_pixel((col % tile_size) as u32, (row % tile_size) as u32, color);
+    }
+
+    for (&(x, y), tile) in &tiles {
+        let dir = output_dir.join(zoom.to_string()).join(x.to_string());
+        fs::create_dir_all(&dir)?;
+        tile.save(dir.join(format!("{}.png", y)))?;
+    }
+
+    Ok(tiles.len())
+}
+
+/// Write a tile pyramid of `matrix` and a viewer into `output_dir`.
+///
+/// Only the `verbosity`, `transform`, `clip` and `categories` of
+/// [Config](crate::Config) are used, cells are colored like in [plot](crate::plot).
+/// Percentiles are taken for each zoom level.
+pub fn write_tile_pyramid<T>(
+    matrix: &SparseMatrix<T>,
+    config: &Config,
+    tile_config: &TileConfig,
+    output_dir: &str
+) -> ImageResult<()>
+where T: ToPrimitive
+{
+    if tile_config.tile_size == 0 {
+        return Err(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic(
+            "[ndarray-to-img::write_tile_pyramid] tile size must be positive".to_string()
+        ))));
+    }
+
+    let output_dir = Path::new(output_dir);
+    let max_zoom = max_zoom(matrix.nrows, matrix.ncols, tile_config.tile_size);
+
+    if config.verbosity > 0 {
+        eprintln!("Generating tile pyramid {}", output_dir.display());
+        if config.verbosity > 1 {
+            eprintln!("zoom levels: {}", max_zoom + 1);
+        }
+    }
+
+    fs::create_dir_all(output_dir)?;
+
+    // deepest level, one cell per pixel
+    let mut level: Level = HashMap::with_capacity(matrix.entries.len());
+    for (row, col, value) in &matrix.entries {
+        let value = value.to_f64().unwrap();
+        level
+            .entry((*row, *col))
+            .and_modify(|a| a.merge(&Accumulator::new(value)))
+            .or_insert_with(|| Accumulator::new(value));
+    }
+
+    for zoom in (0..=max_zoom).rev() {
+        let tile_count = write_level(&level, zoom, tile_config, config, output_dir)?;
+
+        if config.verbosity > 1 {
+            eprintln!("zoom level {}: {} tiles", zoom, tile_count);
+        }
+
+        if zoom > 0 {
+            level = coarsen(&level);
+        }
+    }
+
+    let viewer = VIEWER
+        .replace("{{ROWS}}", &matrix.nrows.to_string())
+        .replace("{{COLS}}", &matrix.ncols.to_string())
+        .replace("{{TILE_SIZE}}", &tile_config.tile_size.to_string())
+        .replace("{{MAX_ZOOM}}", &max_zoom.to_string());
+    fs::write(output_dir.join("index.html"), viewer)?;
+
+    Ok(())
+}
+
+const VIEWER: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>ndarray-to-img</title>
+<style>
+  html, body { margin: 0; height: 100%; overflow: hidden; background: #fff; font-family: monospace; }
+  canvas { display: block; cursor: grab; }
+  #info { position: fixed; right: 8px; top: 8px; padding: 2px 6px; background: rgba(238, 238, 238, 0.9); font-size: 12px; white-space: pre; }
+</style>
+</head>
+<body>
+<canvas id="view"></canvas>
+<div id="info"></div>
+<script>
+(function () {
+  var ROWS = {{ROWS}};
+  var COLS = {{COLS}};
+  var TILE_SIZE = {{TILE_SIZE}};
+  var MAX_ZOOM = {{MAX_ZOOM}};
+
+  var canvas = document.getElementById("view");
+  var ctx = canvas.getContext("2d");
+  var info = document.getElementById("info");
+
+  // screen pixels per cell and screen position of cell (0, 0)
+  var scale, offsetX, offsetY;
+  var tiles = {};
+  var dragging = false, lastX = 0, lastY = 0, mouseX = 0, mouseY = 0;
+
+  function reset() {
+    scale = Math.min(window.innerWidth / COLS, window.innerHeight / ROWS);
+    offsetX = 0; offsetY = 0;
+    draw();
+  }
+
+  function tile(z, x, y) {
+    var key = z + "/" + x + "/" + y;
+    if (!(key in tiles)) {
+      var img = new Image();
+      img.onload = draw;
+      img.onerror = function () { img.missing = true; };
+      img.src = key + ".png";
+      tiles[key] = img;
+    }
+    return tiles[key];
+  }
+
+  function draw() {
+    canvas.width = window.innerWidth;
+    canvas.height = window.innerHeight;
+    ctx.imageSmoothingEnabled = false;
+
+    // the deepest level whose pixels are still at least one screen pixel
+    var zoom = Math.max(0, Math.min(MAX_ZOOM, Math.floor(MAX_ZOOM + Math.log2(scale))));
+    var cellsPerPixel = Math.pow(2, MAX_ZOOM - zoom);
+    var span = TILE_SIZE * cellsPerPixel * scale;
+
+    var x0 = Math.max(0, Math.floor(-offsetX / span));
+    var y0 = Math.max(0, Math.floor(-offsetY / span));
+    var x1 = Math.min(Math.ceil(COLS / (TILE_SIZE * cellsPerPixel)), Math.ceil((canvas.width - offsetX) / span));
+    var y1 = Math.min(Math.ceil(ROWS / (TILE_SIZE * cellsPerPixel)), Math.ceil((canvas.height - offsetY) / span));
+
+    for (var x = x0; x < x1; x++) {
+      for (var y = y0; y < y1; y++) {
+        var img = tile(zoom, x, y);
+        if (img.complete && !img.missing && img.naturalWidth > 0) {
+          ctx.drawImage(img, offsetX + x * span, offsetY + y * span, span, span);
+        }
+      }
+    }
+
+    ctx.strokeStyle = "#00f";
+    ctx.strokeRect(offsetX, offsetY, COLS * scale, ROWS * scale);
+
+    var row = Math.floor((mouseY - offsetY) / scale);
+    var col = Math.floor((mouseX - offsetX) / scale);
+    var position = (row >= 0 && col >= 0 && row < ROWS && col < COLS) ? "\nrow: " + row + " col: " + col : "";
+    info.textContent = ROWS + " x " + COLS + " zoom level " + zoom + "/" + MAX_ZOOM + position;
+  }
+
+  canvas.addEventListener("wheel", function (e) {
+    e.preventDefault();
+    var factor = e.deltaY < 0 ? 1.25 : 1 / 1.25;
+    offsetX = e.clientX - (e.clientX - offsetX) * factor;
+    offsetY = e.clientY - (e.clientY - offsetY) * factor;
+    scale *= factor;
+    draw();
+  }, { passive: false });
+
+  canvas.addEventListener("mousedown", function (e) {
+    dragging = true; lastX = e.clientX; lastY = e.clientY;
+  });
+
+  window.addEventListener("mouseup", function () { dragging = false; });
+
+  canvas.addEventListener("mousemove", function (e) {
+    mouseX = e.clientX; mouseY = e.clientY;
+    if (dragging) {
+      offsetX += e.clientX - lastX; offsetY += e.clientY - lastY;
+      lastX = e.clientX; lastY = e.clientY;
+    }
+    draw();
+  });
+
+  canvas.addEventListener("dblclick", reset);
+  window.addEventListener("resize", draw);
+
+  reset();
+})();
+</script>
+</body>
+</html>
+"##;
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::tests_prelude;
+
+    #[test]
+    fn test_max_zoom() {
+        assert_eq!(max_zoom(0, 0, 256), 0);
+        assert_eq!(max_zoom(256, 10, 256), 0);
+        assert_eq!(max_zoom(257, 10, 256), 1);
+        assert_eq!(max_zoom(1_000_000, 1_000_000, 256), 12);
+    }
+
+    #[test]
+    fn test_write_tile_pyramid() {
+		let config = tests_prelude::config();
+
+        let mut matrix: SparseMatrix<i32> = SparseMatrix::new(1000, 1000);
+        for i in 0..1000 {
+            matrix.push(i, i, 1);
+        }
+        matrix.push(10, 900, -190);
+
+        let tile_config = TileConfig { tile_size: 256, aggregation: Aggregation::Mean };
+        let dir = "test_tiles";
+        assert_eq!(write_tile_pyramid(&matrix, &config, &tile_config, dir).unwrap(), ());
+
+        let dir = Path::new(dir);
+        assert!(dir.join("index.html").exists());
+        assert!(dir.join("0/0/0.png").exists());
+        // the lone cell in the top right, and nothing below it
+        assert!(dir.join("2/3/0.png").exists());
+        assert!(!dir.join("2/3/1.png").exists());
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_dir_all(dir).unwrap(), ());
+		}
+    }
+
+    #[test]
+    fn test_write_tile_pyramid_zero_tile_size() {
+        let matrix: SparseMatrix<i32> = SparseMatrix::new(10, 10);
+        let tile_config = TileConfig { tile_size: 0, aggregation: Aggregation::Mean };
+
+        assert!(write_tile_pyramid(&matrix, &tests_prelude::config(), &tile_config, "test_tiles_empty").is_err());
+        assert!(!Path::new("test_tiles_empty").exists());
+    }
+}
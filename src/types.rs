@@ -1,5 +1,9 @@
 //! Types
 
+use std::ops::Range;
+
+use image::Rgba;
+
 /// Configuration for the output image.
 #[derive(Clone)]
 pub struct Config {
@@ -9,4 +13,182 @@ pub struct Config {
 	pub draw_diagonal: bool,
 	pub draw_boundaries: bool, // draw row and column boundaries?
 	pub scaling_factor: u8,
+	pub viewport: Option<Viewport>, // render only a window of the matrix?
+	pub band: Option<Band>, // render only cells near the diagonal?
+	pub transform: Transform, // applied to values before coloring them
+	pub clip: Option<Clip>, // fixed limits for the colors?
+	pub categories: Option<Categories>, // color values as categories?
+}
+
+impl Default for Config {
+	/// Colored, not annotated and not scaled, values drawn as they are
+	fn default() -> Self {
+		Config {
+			verbosity: 0,
+			with_color: true,
+			annotate_image: false,
+			draw_diagonal: false,
+			draw_boundaries: false,
+			scaling_factor: 1,
+			viewport: None,
+			band: None,
+			transform: Transform::Linear,
+			clip: None,
+			categories: None,
+		}
+	}
+}
+
+/// A window of the matrix to render.
+///
+/// Rows and columns are cells of the unscaled matrix.
+/// The image gets axis labels with the indices of the original matrix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Viewport {
+	pub rows: Range<usize>,
+	pub cols: Range<usize>,
+	/// Colors relative to the whole matrix (`true`) or only to the window (`false`)
+	pub global_scale: bool,
+}
+
+impl Viewport {
+	/// The window in a matrix of `dim` (rows, cols) that has been scaled by
+	/// `scaling_factor`, clamped to the matrix.
+	pub(crate) fn scaled(&self, dim: (usize, usize), scaling_factor: u8) -> (Range<usize>, Range<usize>) {
+		let scaling_factor = scaling_factor as usize;
+		let clamp = |range: &Range<usize>, len: usize| {
+			let start = (range.start * scaling_factor).min(len);
+			let end = (range.end * scaling_factor).clamp(start, len);
+			start..end
+		};
+
+		(clamp(&self.rows, dim.0), clamp(&self.cols, dim.1))
+	}
+}
+
+/// Cells near the diagonal to render, those with `lo <= col - row <= hi`.
+///
+/// A sheared band is drawn as a strip of `hi - lo + 1` columns, one per
+/// diagonal, with the rows in place.
+/// When sheared, the columns of a [Viewport] are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Band {
+	pub lo: isize,
+	pub hi: isize,
+	pub shear: bool,
+}
+
+impl Band {
+	/// The cells with `|row - col| <= k`
+	pub fn symmetric(k: usize, shear: bool) -> Self {
+		Band { lo: -(k as isize), hi: k as isize, shear }
+	}
+
+	/// Is the cell at `[row, col]` in the band?
+	pub fn contains(&self, row: usize, col: usize) -> bool {
+		let diagonal = col as isize - row as isize;
+		self.lo <= diagonal && diagonal <= self.hi
+	}
+
+	/// Is the pixel at (x, y) of a matrix scaled by `scaling_factor` in the band?
+	/// Boundaries count for the cells on both sides.
+	pub(crate) fn contains_pixel(&self, x: usize, y: usize, scaling_factor: usize) -> bool {
+		let (row, col) = (y / scaling_factor, x / scaling_factor);
+		let above = y.is_multiple_of(scaling_factor) && row > 0;
+		let left = x.is_multiple_of(scaling_factor) && col > 0;
+
+		self.contains(row, col)
+			|| (above && self.contains(row - 1, col))
+			|| (left && self.contains(row, col - 1))
+			|| (above && left && self.contains(row - 1, col - 1))
+	}
+}
+
+/// A transform applied to values, and to the min and max they are colored
+/// relative to, before they are mapped to colors.
+///
+/// Transforms keep the sign of a value, so positive and negative values are
+/// still told apart; they apply to the magnitude.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+	/// Color proportional to the value
+	Linear,
+	/// `log10(|v|)`, magnitudes below 1 are drawn as 0
+	Log10,
+	/// `ln(1 + |v|)`
+	Log1p,
+	/// Linear within `linear_threshold` of 0 and logarithmic beyond it,
+	/// `1 + log10(|v| / linear_threshold)`.
+	/// `linear_threshold` must be positive.
+	SymLog { linear_threshold: f64 },
+	/// `|v|^gamma`, a `gamma` below 1 brings out small values
+	Power { gamma: f64 },
+	/// The fraction of values of the same sign that are at most `|v|`,
+	/// spreads colors evenly whatever the distribution of values
+	Rank,
+}
+
+impl Transform {
+	/// Transform a single value.
+	///
+	/// [Rank](Transform::Rank) depends on all the values and is left to the
+	/// caller, values are returned as they are.
+	pub fn apply(&self, value: f64) -> f64 {
+		let magnitude = value.abs();
+		let transformed = match *self {
+			Transform::Linear | Transform::Rank => magnitude,
+			Transform::Log10 => magnitude.log10().max(0.0),
+			Transform::Log1p => magnitude.ln_1p(),
+			Transform::SymLog { linear_threshold } => {
+				if magnitude <= linear_threshold {
+					magnitude / linear_threshold
+				} else {
+					1.0 + (magnitude / linear_threshold).log10()
+				}
+			},
+			Transform::Power { gamma } => magnitude.powf(gamma),
+		};
+
+		transformed.copysign(value)
+	}
+}
+
+/// Limits of the values that are colored, values outside them are clipped.
+///
+/// Values are still colored relative to 0, so a `min` above 0 only clips
+/// smaller values and does not make them lighter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limits {
+	/// Fixed limits, for images that can be compared with each other
+	Values { min: f64, max: f64 },
+	/// Percentiles, from 0 to 100, of the non-empty values of the matrix.
+	/// `Percentiles { lo: 1.0, hi: 99.0 }` keeps outliers from washing out
+	/// the rest of the matrix.
+	Percentiles { lo: f64, hi: f64 },
+}
+
+/// Fixed limits for the colors, used instead of the min and max of the matrix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clip {
+	pub limits: Limits,
+	/// Color of values below the limits, `None` colors them like the lower limit
+	pub under_color: Option<Rgba<u8>>,
+	/// Color of values above the limits, `None` colors them like the upper limit
+	pub over_color: Option<Rgba<u8>>,
+}
+
+/// Color values as categories, such as cluster ids or traceback directions,
+/// rather than as magnitudes.
+///
+/// Values are rounded to integers, each gets its own color. The
+/// [Transform] and [Clip] are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Categories {
+	/// Colors of given values, other values get colors from a qualitative
+	/// palette in increasing order
+	pub colors: Vec<(i64, Rgba<u8>)>,
+	/// Names of given values in the legend, other values are shown as numbers
+	pub labels: Vec<(i64, String)>,
+	/// Draw a legend listing each category to the right of the image
+	pub legend: bool,
 }
@@ -0,0 +1,154 @@
+//! WebAssembly bindings
+//!
+//! Built with the `wasm` feature as a `cdylib` for `wasm32-unknown-unknown`,
+//! see the README, to draw matrices in a web page without a server.
+//! Nothing is read from or written to a filesystem, [render] returns the
+//! pixels for an `ImageData`:
+//!
+//! ```js
+//! const config = new Config();
+//! config.scaling_factor = 10;
+//! const image = render(new Float64Array([0, 1, 2, 0]), 2, 2, config);
+//! const data = new ImageData(image.pixels, image.width, image.height);
+//! canvas.getContext("2d").putImageData(data, 0, 0);
+//! ```
+//!
+//! Values are laid out row by row, zeros and NaNs are empty like in a
+//! [Matrix](crate::plot::Matrix).
+
+use ndarray::Array2;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+
+use crate::plot::{Matrix, Plottable};
+use crate::types;
+
+
+/// Configuration for the output image, see [Config](crate::Config)
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub verbosity: u8,
+    pub with_color: bool,
+    pub annotate_image: bool,
+    pub draw_diagonal: bool,
+    pub draw_boundaries: bool,
+    pub scaling_factor: u8,
+}
+
+#[wasm_bindgen]
+impl Config {
+    /// Colored, not annotated and not scaled
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Config {
+        Config {
+            verbosity: 0,
+            with_color: true,
+            annotate_image: false,
+            draw_diagonal: false,
+            draw_boundaries: false,
+            scaling_factor: 1,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
+impl Config {
+    fn to_config(self) -> types::Config {
+        types::Config {
+            verbosity: self.verbosity,
+            with_color: self.with_color,
+            annotate_image: self.annotate_image,
+            draw_diagonal: self.draw_diagonal,
+            draw_boundaries: self.draw_boundaries,
+            scaling_factor: self.scaling_factor,
+            ..Default::default()
+        }
+    }
+}
+
+/// Pixels of a rendered matrix, 4 bytes (RGBA) each, row by row
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Image {
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// A copy of the pixels as a `Uint8ClampedArray`
+    #[wasm_bindgen(getter)]
+    pub fn pixels(&self) -> Clamped<Vec<u8>> {
+        Clamped(self.pixels.clone())
+    }
+}
+
+/// Draw the `nrow` x `ncol` matrix whose `values` are laid out row by row
+#[wasm_bindgen]
+pub fn render(values: &[f64], nrow: usize, ncol: usize, config: &Config) -> Result<Image, JsError> {
+    // usize is 32 bits on wasm32, where the product easily overflows
+    let len = nrow.checked_mul(ncol).ok_or_else(|| {
+        JsError::new(&format!("[ndarray-to-img::render] a {}x{} matrix is too large", nrow, ncol))
+    })?;
+    if values.len() != len {
+        return Err(JsError::new(&format!(
+            "[ndarray-to-img::render] expected {} values for a {}x{} matrix, found {}",
+            len, nrow, ncol, values.len()
+        )));
+    }
+    if config.scaling_factor == 0 {
+        return Err(JsError::new("[ndarray-to-img::render] scaling factor is 0"));
+    }
+
+    // NaNs are 0 so that they are empty too
+    let matrix = Matrix {
+        matrix: Array2::from_shape_fn((nrow, ncol), |(row, col)| {
+            let value = values[row * ncol + col];
+            if value.is_nan() { 0.0 } else { value }
+        })
+    };
+
+    let config = config.to_config();
+    let img = matrix.scaled(&config).render(&config);
+    let (width, height) = img.dimensions();
+
+    Ok(Image { width, height, pixels: img.into_raw() })
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::constants::colors::*;
+
+    #[test]
+    fn test_render() {
+        let config = Config { scaling_factor: 10, ..Config::new() };
+        let image = render(&[0.0, 1.0, f64::NAN, 0.0, 2.0, 0.0], 2, 3, &config).unwrap();
+
+        // one more pixel for the closing boundary
+        assert_eq!((image.width, image.height), (31, 21));
+        let pixel = |x: usize, y: usize| &image.pixels[(y * 31 + x) * 4..(y * 31 + x) * 4 + 4];
+        assert_eq!(pixel(5, 5), &WHITE.0);
+        assert_eq!(pixel(25, 5), &WHITE.0);
+        assert_ne!(pixel(15, 5), &WHITE.0);
+        assert_ne!(pixel(15, 5), pixel(15, 15));
+    }
+}
@@ -0,0 +1,148 @@
+//! Wavefront alignment
+//!
+//! Wavefront alignment (WFA) keeps, for each score, the furthest reaching
+//! offset on each diagonal rather than a dense DP matrix.
+//! [Wavefronts](self::Wavefronts) takes these as they are and plots each
+//! furthest reaching point as a cell of the DP matrix colored by its score.
+//!
+//! Diagonal `k` is `target_position - query_position` and the offset is the
+//! position in the target, so the point `(k, offset)` is the cell
+//! `[offset - k, offset]` with the query along the rows.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::plot::{min_and_max, CellSource};
+
+
+/// Furthest reaching points of a wavefront alignment, stored sparsely
+#[derive(Debug, Clone)]
+pub struct Wavefronts {
+    query_len: usize,
+    target_len: usize,
+    /// lowest score reaching each `(query_position, target_position)`
+    cells: HashMap<(usize, usize), i32>,
+}
+
+impl Wavefronts {
+    /// No wavefronts yet for aligning a query of `query_len` against a target
+    /// of `target_len`; the DP matrix has one more row and column than that.
+    pub fn new(query_len: usize, target_len: usize) -> Self {
+        Wavefronts { query_len, target_len, cells: HashMap::new() }
+    }
+
+    /// Add the wavefront of `score` spanning diagonals `lo..=hi`.
+    ///
+    /// `offsets[i]` is the furthest reaching offset of diagonal `lo + i`,
+    /// negative offsets mark null diagonals. Points outside the DP matrix are
+    /// skipped and a cell reached by several scores keeps the lowest.
+    pub fn push(&mut self, score: i32, lo: isize, hi: isize, offsets: &[i32]) {
+        if hi - lo + 1 != offsets.len() as isize {
+            panic!(
+                "[ndarray-to-img::Wavefronts::push] expected {} offsets for diagonals {}..={} found {}",
+                hi - lo + 1, lo, hi, offsets.len()
+            );
+        }
+
+        for (diagonal, &offset) in (lo..=hi).zip(offsets) {
+            if offset < 0 {
+                continue;
+            }
+
+            let target_position = offset as isize;
+            let query_position = target_position - diagonal;
+            if query_position < 0
+                || query_position as usize > self.query_len
+                || target_position as usize > self.target_len
+            {
+                continue;
+            }
+
+            self.cells
+                .entry((query_position as usize, target_position as usize))
+                .and_modify(|s| *s = (*s).min(score))
+                .or_insert(score);
+        }
+    }
+}
+
+impl CellSource<i32> for Wavefronts {
+    fn dim(&self) -> (usize, usize) {
+        (self.query_len + 1, self.target_len + 1)
+    }
+
+    /// The lowest score reaching the cell, `None` if no wavefront reaches it
+    fn value_at(&self, row: usize, col: usize) -> Option<i32> {
+        self.cells.get(&(row, col)).copied()
+    }
+
+    /// Only looks at the cells reached by a wavefront
+    fn max_and_min_in(&self, rows: Range<usize>, cols: Range<usize>) -> (i32, i32) {
+        let scores = self
+            .cells
+            .iter()
+            .filter(|(&(row, col), _)| rows.contains(&row) && cols.contains(&col))
+            .map(|(_, &score)| score);
+
+        min_and_max(scores)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+    use crate::constants::colors::*;
+    use crate::plot::Plottable;
+    use crate::tests_prelude;
+    use image::Rgba;
+
+    fn wavefronts() -> Wavefronts {
+        let mut wavefronts = Wavefronts::new(8, 9);
+        wavefronts.push(0, 0, 0, &[3]);
+        wavefronts.push(4, -1, 1, &[2, 5, 4]);
+        // null diagonal, past the end and a cell reached again
+        wavefronts.push(8, -2, 2, &[-1, 3, 5, 20, 5]);
+        wavefronts
+    }
+
+    #[test]
+    fn test_wavefront_cells() {
+        let wavefronts = wavefronts();
+
+        assert_eq!(wavefronts.dim(), (9, 10));
+        assert_eq!(wavefronts.value_at(3, 3), Some(0));
+        assert_eq!(wavefronts.value_at(3, 2), Some(4));
+        assert_eq!(wavefronts.value_at(5, 5), Some(4));
+        assert_eq!(wavefronts.value_at(3, 4), Some(4));
+        assert_eq!(wavefronts.value_at(4, 3), Some(8));
+        assert_eq!(wavefronts.value_at(3, 5), Some(8));
+        assert_eq!(wavefronts.value_at(0, 0), None);
+        assert_eq!(wavefronts.max_and_min(), (0, 8));
+
+        let scaled = wavefronts.scaled(&tests_prelude::config());
+        assert_eq!(scaled.dim(), (90, 100));
+        assert_eq!(scaled.value_at(39, 30), Some(0));
+    }
+
+    #[test]
+    fn test_plot_wavefronts() {
+		let config = tests_prelude::config();
+
+        let wavefronts = wavefronts();
+        let scaled = wavefronts.scaled(&config);
+		let image_name = "test_wavefront_image.png";
+        assert_eq!(scaled.plot(&config, image_name).unwrap(), ());
+
+        // score 0 is the lowest score, not an empty cell
+        let img = scaled.render(&config);
+        assert_eq!(*img.get_pixel(36, 34), Rgba([0, 0, 0, 255]));
+        assert_eq!(*img.get_pixel(24, 34), Rgba([255, 0, 0, 128]));
+        assert_eq!(*img.get_pixel(36, 44), Rgba([255, 0, 0, 255]));
+        assert_eq!(*img.get_pixel(6, 4), WHITE);
+
+		if tests_prelude::CLEANUP_TESTS {
+			assert_eq!(std::fs::remove_file(image_name).unwrap(), ());
+		}
+    }
+}